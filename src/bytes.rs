@@ -0,0 +1,50 @@
+//! Conversions between `OsStr` and raw bytes.
+//!
+//! On unix an `OsStr` is an arbitrary byte string, so keys and values pass
+//! through unchanged. Elsewhere they are converted lossily.
+
+use std::ffi::{OsStr, OsString};
+
+#[cfg(unix)]
+pub fn as_bytes(s: &OsStr) -> &[u8] {
+    use std::os::unix::ffi::OsStrExt;
+    s.as_bytes()
+}
+
+#[cfg(not(unix))]
+pub fn as_bytes(s: &OsStr) -> &[u8] {
+    s.as_encoded_bytes()
+}
+
+#[cfg(unix)]
+pub fn to_os_string(b: Vec<u8>) -> OsString {
+    use std::os::unix::ffi::OsStringExt;
+    OsString::from_vec(b)
+}
+
+#[cfg(not(unix))]
+pub fn to_os_string(b: Vec<u8>) -> OsString {
+    OsString::from(String::from_utf8_lossy(&b).into_owned())
+}
+
+/// Replace every byte that is not part of valid UTF-8 with a `\xNN` escape.
+pub fn escape_invalid(b: &[u8]) -> String {
+    let mut s = String::new();
+    for chunk in b.utf8_chunks() {
+        s.push_str(chunk.valid());
+        for byte in chunk.invalid() {
+            s.push_str(&format!("\\x{:02X}", byte));
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_invalid() {
+        assert_eq!(escape_invalid(b"a\xFFb\xC3\xA9\xE2\x82"), "a\\xFFb\u{e9}\\xE2\\x82");
+    }
+}
@@ -0,0 +1,136 @@
+//! Built-in denylist of well-known CI and cloud credentials.
+//!
+//! Runners put tokens into the environment of every job. Dumps that take the
+//! whole environment, that is without `--source`, drop these unless the
+//! template asks for them by name. The list is versioned so that a change in
+//! what gets dropped shows up in the note printed to stderr.
+
+use std::ffi::OsString;
+
+use crate::bytes;
+use crate::filter::Pattern;
+use crate::provenance::{Origin, Sourced};
+
+pub const VERSION: u32 = 1;
+
+/// Names and globs, matched against the name of the variable in the
+/// environment and against the key it becomes once prefixes are stripped.
+const PATTERNS: &[&str] = &[
+    // GitHub Actions
+    "GITHUB_TOKEN",
+    "GH_TOKEN",
+    "ACTIONS_RUNTIME_TOKEN",
+    "ACTIONS_ID_TOKEN_REQUEST_TOKEN",
+    "ACTIONS_*_TOKEN",
+    // GitLab CI
+    "CI_JOB_TOKEN",
+    "CI_BUILD_TOKEN",
+    "CI_JOB_JWT*",
+    "CI_REGISTRY_PASSWORD",
+    "CI_DEPLOY_PASSWORD",
+    "CI_DEPENDENCY_PROXY_PASSWORD",
+    "CI_REPOSITORY_URL",
+    "GITLAB_TOKEN",
+    // Jenkins, Buildkite, CircleCI
+    "JENKINS_API_TOKEN",
+    "BUILDKITE_AGENT_ACCESS_TOKEN",
+    "CIRCLE_TOKEN",
+    // Cloud providers
+    "AWS_SECRET_ACCESS_KEY",
+    "AWS_SESSION_TOKEN",
+    "*_SECRET_ACCESS_KEY",
+    "AZURE_CLIENT_SECRET",
+    "ARM_CLIENT_SECRET",
+    "GOOGLE_CREDENTIALS",
+    // Registries and services
+    "NPM_TOKEN",
+    "NODE_AUTH_TOKEN",
+    "CARGO_REGISTRY_TOKEN",
+    "TWINE_PASSWORD",
+    "DOCKER_PASSWORD",
+    "DOCKER_AUTH_CONFIG",
+    "CODECOV_TOKEN",
+    "SONAR_TOKEN",
+    "VAULT_TOKEN",
+    "HEROKU_API_KEY",
+];
+
+pub struct Denylist(Vec<Pattern>);
+
+impl Denylist {
+    pub fn builtin() -> Self {
+        Denylist(PATTERNS.iter().map(|p| Pattern::parse(p).expect("valid built-in pattern")).collect())
+    }
+
+    pub fn is_match(&self, key: &[u8]) -> bool {
+        self.0.iter().any(|p| p.is_match(key))
+    }
+
+    /// Split `items` into the allowed items and the environment names of the
+    /// denied ones. Keys listed in `keep` are always allowed.
+    pub fn split(&self, items: Sourced, keep: &[OsString]) -> (Sourced, Vec<OsString>) {
+        let mut denied = Vec::new();
+        let allowed = items
+            .into_iter()
+            .filter(|(k, (_, origin))| {
+                let name = match origin {
+                    Origin::Env { name } => name,
+                    Origin::Template { .. } => k,
+                };
+                let matched = self.is_match(bytes::as_bytes(k)) || self.is_match(bytes::as_bytes(name));
+                let deny = matched && !keep.contains(k);
+                if deny {
+                    denied.push(name.clone());
+                }
+                !deny
+            })
+            .collect();
+        (allowed, denied)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Environment items, named `name` in the environment and `key` after prefixes are stripped.
+    fn sourced(xs: &[(&str, &str)]) -> Sourced {
+        xs.iter()
+            .map(|(name, key)| (OsString::from(key), (OsString::from("x"), Origin::Env { name: name.into() })))
+            .collect()
+    }
+
+    fn keys(items: &Sourced) -> Vec<OsString> {
+        items.iter().map(|(k, _)| k.clone()).collect()
+    }
+
+    #[test]
+    fn test_split() {
+        let items = sourced(&[
+            ("GITHUB_TOKEN", "GITHUB_TOKEN"),
+            ("ACTIONS_CACHE_TOKEN", "ACTIONS_CACHE_TOKEN"),
+            ("CI_JOB_JWT_V2", "CI_JOB_JWT_V2"),
+            ("AWS_SECRET_ACCESS_KEY", "AWS_SECRET_ACCESS_KEY"),
+            ("APP_TOKEN", "APP_TOKEN"),
+            ("CI_JOB_ID", "CI_JOB_ID"),
+        ]);
+        let keep = vec![OsString::from("AWS_SECRET_ACCESS_KEY")];
+        let (allowed, denied) = Denylist::builtin().split(items, &keep);
+        assert_eq!(keys(&allowed), vec!["AWS_SECRET_ACCESS_KEY", "APP_TOKEN", "CI_JOB_ID"]);
+        assert_eq!(denied, vec!["GITHUB_TOKEN", "ACTIONS_CACHE_TOKEN", "CI_JOB_JWT_V2"]);
+    }
+
+    #[test]
+    fn test_split_stripped() {
+        // As with `-p CI_ -p AWS_ -p STAGING_`.
+        let items = sourced(&[
+            ("CI_JOB_TOKEN", "JOB_TOKEN"),
+            ("AWS_SECRET_ACCESS_KEY", "SECRET_ACCESS_KEY"),
+            ("STAGING_GITHUB_TOKEN", "GITHUB_TOKEN"),
+            ("CI_JOB_ID", "JOB_ID"),
+        ]);
+        let (allowed, denied) = Denylist::builtin().split(items, &[]);
+        assert_eq!(keys(&allowed), vec!["JOB_ID"]);
+        assert_eq!(denied, vec!["CI_JOB_TOKEN", "AWS_SECRET_ACCESS_KEY", "STAGING_GITHUB_TOKEN"]);
+    }
+}
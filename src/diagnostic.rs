@@ -0,0 +1,82 @@
+//! Template parse diagnostics with file, line and column information.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// A problem found by a template parser, located by line and column (both 1-based).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(line: usize, column: usize, message: impl Into<String>) -> Self {
+        Diagnostic { line, column, message: message.into() }
+    }
+}
+
+/// A `Diagnostic` attached to the template it was found in.
+/// Renders as the message, the location and a snippet of the offending line.
+#[derive(Debug)]
+pub struct ParseError {
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+    pub source_line: String,
+}
+
+impl ParseError {
+    pub fn new(path: &Path, input: &[u8], diagnostic: Diagnostic) -> Self {
+        let source_line = input.split(|b| *b == b'\n').nth(diagnostic.line - 1).unwrap_or_default();
+        let source_line = String::from_utf8_lossy(source_line.strip_suffix(b"\r").unwrap_or(source_line));
+        ParseError {
+            path: path.to_path_buf(),
+            line: diagnostic.line,
+            column: diagnostic.column,
+            message: diagnostic.message,
+            source_line: source_line.into_owned(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let gutter = " ".repeat(self.line.to_string().len());
+        // Keep tabs so the caret lines up with the snippet.
+        let pad: String = self
+            .source_line
+            .chars()
+            .take(self.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        writeln!(f, "{}", self.message)?;
+        writeln!(f, "{}--> {}:{}:{}", gutter, self.path.display(), self.line, self.column)?;
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", self.line, self.source_line)?;
+        write!(f, "{} | {}^", gutter, pad)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let input = b"A=1\nDB_HOST: localhost\n";
+        let error = ParseError::new(Path::new("app.env"), input, Diagnostic::new(2, 8, "expected `=`, found `:`"));
+        let expect = concat!(
+            "expected `=`, found `:`\n",
+            " --> app.env:2:8\n",
+            "  |\n",
+            "2 | DB_HOST: localhost\n",
+            "  |        ^",
+        );
+        assert_eq!(error.to_string(), expect);
+    }
+}
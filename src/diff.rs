@@ -0,0 +1,78 @@
+//! `dump-env diff TEMPLATE`: how the environment differs from a template.
+//!
+//! Every key of the full merge gets one line, unless the environment sets it
+//! to the template default:
+//!
+//! * `- KEY` is only in the template, so the default is used.
+//! * `~ KEY` is in both, and the environment overrides the default.
+//! * `+ KEY` is only in the environment.
+//!
+//! Template defaults are compared and shown as written, without `$$` escapes.
+
+use std::ffi::OsStr;
+
+use crate::interpolate;
+use crate::provenance::{Origin, Provenance};
+use crate::template::Entry;
+use crate::EnvItem;
+
+/// The lines of the diff. `mask` hides values that should not be shown.
+pub fn lines(entries: &[Entry], items: &[EnvItem], provenance: &Provenance, mask: impl Fn(&OsStr, &OsStr) -> String) -> Vec<String> {
+    let mut lines = Vec::new();
+    for (k, v) in items {
+        let key = k.to_string_lossy();
+        let entry = entries.iter().find(|e| &e.key == k);
+        let default = entry.map(|e| interpolate::unescape(&e.value));
+        match (provenance.get(k), entry, default) {
+            (Some(Origin::Env { name }), Some(entry), Some(default)) if default != *v => lines.push(format!(
+                "~ {} (line {}): `{}` in the template, `{}` from env {}",
+                key,
+                entry.line,
+                mask(k, &default),
+                mask(k, v),
+                name.to_string_lossy()
+            )),
+            (Some(Origin::Env { name }), None, _) => {
+                lines.push(format!("+ {}: `{}` from env {}", key, mask(k, v), name.to_string_lossy()))
+            }
+            (Some(Origin::Template { line }), _, _) => {
+                lines.push(format!("- {} (line {}): `{}` from the template", key, line, mask(k, v)))
+            }
+            _ => {}
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provenance::{self, Sourced};
+
+    #[test]
+    fn test_lines() {
+        let entries = vec![
+            Entry { key: "A".into(), value: "1".into(), line: 1, annotations: vec![] },
+            Entry { key: "B".into(), value: "2".into(), line: 2, annotations: vec![] },
+            Entry { key: "C".into(), value: "3".into(), line: 3, annotations: vec![] },
+            Entry { key: "E".into(), value: "$$y".into(), line: 4, annotations: vec![] },
+            Entry { key: "F".into(), value: "$$y".into(), line: 5, annotations: vec![] },
+        ];
+        let items: Sourced = vec![
+            ("A".into(), ("1".into(), Origin::Template { line: 1 })),
+            ("B".into(), ("20".into(), Origin::Env { name: "APP_B".into() })),
+            ("C".into(), ("3".into(), Origin::Env { name: "C".into() })),
+            ("D".into(), ("4".into(), Origin::Env { name: "D".into() })),
+            ("E".into(), ("$y".into(), Origin::Env { name: "E".into() })),
+            ("F".into(), ("$z".into(), Origin::Env { name: "F".into() })),
+        ];
+        let (items, provenance) = provenance::split(items);
+        let lines = lines(&entries, &items, &provenance, |_, v| v.to_string_lossy().into_owned());
+        assert_eq!(lines, vec![
+            "- A (line 1): `1` from the template",
+            "~ B (line 2): `2` in the template, `20` from env APP_B",
+            "+ D: `4` from env D",
+            "~ F (line 5): `$y` in the template, `$z` from env F",
+        ]);
+    }
+}
@@ -0,0 +1,367 @@
+//! Parser for the dotenv template grammar.
+//!
+//! Follows the dialect shared by docker-compose and Node dotenv:
+//!
+//! * `#` starts a comment when it begins a line (after optional whitespace)
+//!   or follows whitespace in an unquoted value.
+//! * Single quoted values are taken literally.
+//! * Double quoted values support `\n`, `\r`, `\t`, `\\`, `\"` and `\$` escapes
+//!   and may span multiple lines.
+//! * Unquoted values are trimmed.
+//! * Keys may be preceded by `export`, so the file can also be sourced by a shell.
+//! * Lines without `=` are skipped and reported as a `Diagnostic`.
+//!
+//! The input is read as bytes, so keys and values that are not valid UTF-8 are
+//! kept as they are.
+//!
+//! Comments directly above a key are kept as its `Annotation`s when they
+//! start with `@`.
+//!
+//! Values are returned ready for the `interpolate` module: a literal `$`, from
+//! a single quoted value or a `\$` escape, is written as `$$`.
+
+use std::iter::Peekable;
+
+use clap::ArgEnum;
+use std::slice::Iter;
+
+use crate::bytes::to_os_string;
+use crate::diagnostic::Diagnostic;
+use crate::template::{Annotation, Entry};
+
+/// Parse the contents of a .env file.
+/// Returns the entries with the diagnostics for skipped or suspicious lines, or
+/// the diagnostic that made the rest of the input unreadable.
+pub fn parse(input: &[u8]) -> Result<(Vec<Entry>, Vec<Diagnostic>), Diagnostic> {
+    parse_dialect(input, Dialect::Dotenv)
+}
+
+/// Line based `KEY=value` grammars that share comments, annotations and keys,
+/// and differ in how values are read.
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    /// docker-compose and Node dotenv files
+    Dotenv,
+    /// systemd `EnvironmentFile=` files
+    Systemd,
+}
+
+pub fn parse_dialect(input: &[u8], dialect: Dialect) -> Result<(Vec<Entry>, Vec<Diagnostic>), Diagnostic> {
+    let mut cursor = Cursor {
+        bytes: input.iter().peekable(),
+        line: 1,
+        column: 1,
+        warnings: Vec::new(),
+    };
+    let mut entries = Vec::new();
+    let mut annotations = Vec::new();
+
+    // Every iteration starts at the beginning of a line.
+    loop {
+        let line = cursor.line;
+        cursor.skip_while(|b| b.is_ascii_whitespace());
+        if cursor.line != line {
+            // A blank line ends the comments that belong to the next key.
+            annotations.clear();
+        }
+        match cursor.peek() {
+            None => break,
+            Some(b';') if dialect == Dialect::Systemd => {
+                cursor.skip_line();
+                continue;
+            }
+            Some(b'#') => {
+                let line = cursor.line;
+                cursor.next();
+                let comment = cursor.take_while(|b| b != b'\n');
+                annotations.extend(Annotation::parse(&String::from_utf8_lossy(&comment), line));
+                cursor.skip_line();
+                continue;
+            }
+            Some(_) => {}
+        }
+
+        let (line, column) = (cursor.line, cursor.column);
+        let key = cursor.take_while(|b| b != b'=' && b != b'\n');
+        if cursor.peek() != Some(b'=') {
+            // No assignment on this line.
+            let key = key.trim_ascii_end();
+            let found = key.iter().position(|b| !is_key_byte(*b));
+            cursor.warn(match found {
+                Some(i) => {
+                    let c = String::from_utf8_lossy(&key[i..]).chars().next().unwrap_or_default();
+                    Diagnostic::new(line, column + char_count(&key[..i]), format!("expected `=`, found `{}`", c))
+                }
+                None => Diagnostic::new(line, column + char_count(key), "expected `=` after key"),
+            });
+            annotations.clear();
+            cursor.skip_line();
+            continue;
+        }
+        let eq_column = cursor.column;
+        cursor.next();
+
+        let (key, value) = match dialect {
+            Dialect::Dotenv => (strip_export(key.trim_ascii()), cursor.value()?),
+            Dialect::Systemd => (key.trim_ascii(), cursor.systemd_value()?),
+        };
+        cursor.skip_line();
+        if key.is_empty() {
+            cursor.warn(Diagnostic::new(line, eq_column, "missing key before `=`"));
+        } else if key.iter().any(u8::is_ascii_whitespace) {
+            let message = format!("invalid key `{}`", String::from_utf8_lossy(key));
+            cursor.warn(Diagnostic::new(line, column, message));
+        } else {
+            entries.push(Entry {
+                key: to_os_string(key.to_vec()),
+                value: to_os_string(value),
+                line,
+                annotations: std::mem::take(&mut annotations),
+            });
+        }
+        annotations.clear();
+    }
+
+    Ok((entries, cursor.warnings))
+}
+
+/// Bytes of a well formed key. Anything outside ASCII is accepted as is.
+fn is_key_byte(b: u8) -> bool {
+    !b.is_ascii() || b.is_ascii_alphanumeric() || matches!(b, b'_' | b'.' | b'-')
+}
+
+/// Number of characters in `b`, counting every byte that does not continue a
+/// UTF-8 sequence.
+fn char_count(b: &[u8]) -> usize {
+    b.iter().filter(|b| (**b as i8) >= -0x40).count()
+}
+
+/// Strip the optional `export` keyword in front of a key.
+fn strip_export(key: &[u8]) -> &[u8] {
+    match key.strip_prefix(b"export") {
+        Some(x) if x.starts_with(b" ") || x.starts_with(b"\t") => x.trim_ascii_start(),
+        _ => key,
+    }
+}
+
+pub(crate) struct Cursor<'a> {
+    bytes: Peekable<Iter<'a, u8>>,
+    pub(crate) line: usize,
+    pub(crate) column: usize,
+    warnings: Vec<Diagnostic>,
+}
+
+impl<'a> Cursor<'a> {
+    pub(crate) fn peek(&mut self) -> Option<u8> {
+        self.bytes.peek().copied().copied()
+    }
+
+    pub(crate) fn next(&mut self) -> Option<u8> {
+        let b = self.bytes.next().copied();
+        match b {
+            Some(b'\n') => {
+                self.line += 1;
+                self.column = 1;
+            }
+            Some(b) if (b as i8) >= -0x40 => self.column += 1,
+            _ => {}
+        }
+        b
+    }
+
+    fn warn(&mut self, diagnostic: Diagnostic) {
+        self.warnings.push(diagnostic);
+    }
+
+    fn skip_while(&mut self, f: impl Fn(u8) -> bool) {
+        while self.peek().is_some_and(&f) {
+            self.next();
+        }
+    }
+
+    fn take_while(&mut self, f: impl Fn(u8) -> bool) -> Vec<u8> {
+        let mut v = Vec::new();
+        while let Some(b) = self.peek().filter(|b| f(*b)) {
+            v.push(b);
+            self.next();
+        }
+        v
+    }
+
+    /// Skip the remainder of the current line, including the newline.
+    fn skip_line(&mut self) {
+        self.skip_while(|b| b != b'\n');
+        self.next();
+    }
+
+    /// Read a value, starting right after the `=` and ending before the end of the line.
+    fn value(&mut self) -> Result<Vec<u8>, Diagnostic> {
+        let indented = matches!(self.peek(), Some(b' ' | b'\t'));
+        self.skip_while(|b| b == b' ' || b == b'\t');
+        let value = match self.peek() {
+            Some(b'#') if indented => {
+                self.skip_while(|b| b != b'\n');
+                Vec::new()
+            }
+            Some(q @ (b'\'' | b'"')) => {
+                let (line, column) = (self.line, self.column);
+                self.next();
+                let value = if q == b'\'' {
+                    self.single_quoted()
+                } else {
+                    self.double_quoted()
+                };
+                let value = value.ok_or_else(|| Diagnostic::new(line, column, "unterminated quoted value"))?;
+                // Anything after the closing quote, except a comment, is ignored with a warning.
+                self.skip_while(|b| b == b' ' || b == b'\t');
+                match self.peek() {
+                    Some(b'#' | b'\r' | b'\n') | None => {}
+                    Some(b) => {
+                        let message = format!("unexpected `{}` after closing quote", b.escape_ascii());
+                        let d = Diagnostic::new(self.line, self.column, message);
+                        self.warn(d);
+                    }
+                }
+                self.skip_while(|b| b != b'\n');
+                value
+            }
+            _ => self.unquoted(),
+        };
+        Ok(value)
+    }
+
+    fn single_quoted(&mut self) -> Option<Vec<u8>> {
+        let value = self.take_while(|b| b != b'\'');
+        self.next()?;
+        let mut escaped = Vec::with_capacity(value.len());
+        for b in value {
+            if b == b'$' {
+                escaped.push(b'$');
+            }
+            escaped.push(b);
+        }
+        Some(escaped)
+    }
+
+    fn double_quoted(&mut self) -> Option<Vec<u8>> {
+        let mut value = Vec::new();
+        loop {
+            match self.next()? {
+                b'"' => return Some(value),
+                b'\\' => match self.next()? {
+                    b'n' => value.push(b'\n'),
+                    b'r' => value.push(b'\r'),
+                    b't' => value.push(b'\t'),
+                    b'$' => value.extend_from_slice(b"$$"),
+                    b @ (b'\\' | b'"') => value.push(b),
+                    b => value.extend_from_slice(&[b'\\', b]),
+                },
+                b => value.push(b),
+            }
+        }
+    }
+
+    fn unquoted(&mut self) -> Vec<u8> {
+        let mut value = Vec::new();
+        while let Some(b) = self.peek().filter(|b| *b != b'\n') {
+            if b == b'#' && matches!(value.last(), Some(b' ' | b'\t')) {
+                self.skip_while(|b| b != b'\n');
+                break;
+            }
+            value.push(b);
+            self.next();
+        }
+        value.trim_ascii().to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::to_os_str;
+    use crate::{template, EnvItems};
+
+    fn parse_items(input: &str) -> EnvItems {
+        template::items(&parse(input.as_bytes()).unwrap().0)
+    }
+
+    #[test]
+    fn test_parse_unquoted() {
+        let input = "# comment\n  # indented comment\na=1\n b = 2 \nc=3 # trailing\nd=x#y\n\ne=\nf= # empty";
+        let expect = to_os_str(vec![("a", "1"), ("b", "2"), ("c", "3"), ("d", "x#y"), ("e", ""), ("f", "")]);
+        assert_eq!(parse_items(input), expect);
+    }
+
+    #[test]
+    fn test_parse_quoted() {
+        let input = concat!(
+            "a='a b # c' # comment\n",
+            "b=\"a b # c\"\n",
+            "c='\\n $x'\n",
+            "d=\"line\\nnext\\t\\\"q\\\" \\\\ \\$x\"\n",
+            "e=\"-----BEGIN KEY-----\nabc\n-----END KEY-----\"\n",
+            "f=g\n",
+        );
+        let expect = to_os_str(vec![
+            ("a", "a b # c"),
+            ("b", "a b # c"),
+            ("c", "\\n $$x"),
+            ("d", "line\nnext\t\"q\" \\ $$x"),
+            ("e", "-----BEGIN KEY-----\nabc\n-----END KEY-----"),
+            ("f", "g"),
+        ]);
+        assert_eq!(parse_items(input), expect);
+    }
+
+    #[test]
+    fn test_parse_export() {
+        let input = "export a=1\nexport\tb='2'\nexport=3\nexported=4\n";
+        let expect = to_os_str(vec![("a", "1"), ("b", "2"), ("export", "3"), ("exported", "4")]);
+        assert_eq!(parse_items(input), expect);
+    }
+
+    #[test]
+    fn test_parse_annotations() {
+        let input = "# @required\na=1\n\n# @type int\n\nb=2\n# The port\n# @type int\n  # @required\nc=3\nd=\"\n\"\ne=4\n";
+        let (entries, _) = parse(input.as_bytes()).unwrap();
+        let annotations: Vec<Vec<(&str, &str, usize)>> = entries
+            .iter()
+            .map(|e| e.annotations.iter().map(|a| (a.name.as_str(), a.argument.as_str(), a.line)).collect())
+            .collect();
+        assert_eq!(annotations, vec![
+            vec![("required", "", 1)],
+            vec![],
+            vec![("type", "int", 8), ("required", "", 9)],
+            vec![],
+            vec![],
+        ]);
+        let lines: Vec<usize> = entries.iter().map(|e| e.line).collect();
+        assert_eq!(lines, vec![2, 6, 10, 11, 13]);
+    }
+
+    #[test]
+    fn test_parse_diagnostics() {
+        let input = "a=1\nDB_HOST: localhost\n  b c=2\n=3\nd='4' x\nnot a pair\ne=5\n";
+        let (entries, warnings) = parse(input.as_bytes()).unwrap();
+        assert_eq!(template::items(&entries), to_os_str(vec![("a", "1"), ("d", "4"), ("e", "5")]));
+        assert_eq!(warnings, vec![
+            Diagnostic::new(2, 8, "expected `=`, found `:`"),
+            Diagnostic::new(3, 3, "invalid key `b c`"),
+            Diagnostic::new(4, 1, "missing key before `=`"),
+            Diagnostic::new(5, 7, "unexpected `x` after closing quote"),
+            Diagnostic::new(6, 4, "expected `=`, found ` `"),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_unterminated_quote() {
+        let result = parse(b"a=1\nb=\"abc\nc=3\n");
+        assert_eq!(result, Err(Diagnostic::new(2, 3, "unterminated quoted value")));
+    }
+
+    #[test]
+    fn test_parse_non_utf8() {
+        let (entries, _) = parse(b"k\xFF=\xFE v\n\xC3\xA9 x=1\n").unwrap();
+        assert_eq!(template::items(&entries), vec![(to_os_string(b"k\xFF".to_vec()), to_os_string(b"\xFE v".to_vec()))]);
+    }
+}
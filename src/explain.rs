@@ -0,0 +1,227 @@
+//! `dump-env explain KEY`: how a single key resolves.
+//!
+//! The trace lists every place the key could take its value from: the
+//! template entry and each environment variable that is, or nearly is, the key
+//! once prefixes are stripped. Variables are renamed by the first prefix they
+//! start with, so a prefix earlier in `--prefixes` can hide a later one.
+
+use std::ffi::OsStr;
+
+use crate::denylist::Denylist;
+use crate::filter::Filter;
+use crate::template::Entry;
+use crate::{bytes, interpolate, matching_prefix, EnvItem, Mode};
+
+pub struct Explain<'a> {
+    pub key: &'a OsStr,
+    pub mode: Mode,
+    /// Path of the template, for display.
+    pub template: Option<&'a str>,
+    pub entries: &'a [Entry],
+    /// The environment as it was, before filtering and stripping prefixes.
+    pub env: &'a [EnvItem],
+    pub prefixes: &'a [String],
+    pub filter: &'a Filter,
+    /// The denylist, if it applies in this mode.
+    pub denylist: Option<&'a Denylist>,
+}
+
+impl Explain<'_> {
+    /// The lines of the trace. `value` is the value the key resolved to, if
+    /// it is part of the output, and `mask` hides values that should not be shown.
+    pub fn trace(&self, value: Option<&OsStr>, mask: impl Fn(&OsStr) -> String) -> Vec<String> {
+        let key = bytes::as_bytes(self.key);
+        let mut lines = vec![self.key.to_string_lossy().into_owned()];
+
+        let entry = self.entries.iter().find(|e| e.key == self.key);
+        match (self.mode, entry) {
+            (Mode::Plain, _) => lines.push(String::from("  no template")),
+            (_, Some(entry)) => {
+                let mut line = format!(
+                    "  template {} line {}: default `{}`",
+                    self.template.unwrap_or_default(),
+                    entry.line,
+                    mask(&interpolate::unescape(&entry.value))
+                );
+                for a in &entry.annotations {
+                    line.push_str(&format!(", @{}", a.name));
+                    if !a.argument.is_empty() {
+                        line.push_str(&format!(" {}", a.argument));
+                    }
+                }
+                lines.push(line);
+            }
+            (_, None) => lines.push(format!("  template {}: no entry", self.template.unwrap_or_default())),
+        }
+
+        if !self.prefixes.is_empty() {
+            lines.push(format!("  prefixes, first match wins: {}", self.prefixes.join(", ")));
+        }
+
+        let mut candidates = Vec::new();
+        for (name, v) in self.env {
+            let raw = bytes::as_bytes(name);
+            let prefix = matching_prefix(self.prefixes, raw);
+            let stripped = prefix.map_or(raw, |p| &raw[p.len()..]);
+            let nearly = self.prefixes.iter().any(|p| raw.strip_prefix(p.as_bytes()) == Some(key));
+            if stripped != key && raw != key && !nearly {
+                continue;
+            }
+
+            // Values that stay out of the output stay out of the trace too.
+            let filtered = !self.filter.is_match(raw);
+            let denied = self.denylist.is_some_and(|d| d.is_match(stripped) || d.is_match(raw))
+                && !self.entries.iter().any(|e| bytes::as_bytes(&e.key) == stripped);
+            let what = if filtered || denied {
+                format!("  env {}=<hidden>: ", name.to_string_lossy())
+            } else {
+                format!("  env {}=`{}`: ", name.to_string_lossy(), mask(v))
+            };
+            let status = if stripped != key {
+                let renamed = String::from_utf8_lossy(stripped);
+                match prefix {
+                    Some(p) => format!("not a candidate, prefix `{}` matched first and renamed it to {}", p, renamed),
+                    None => String::from("not a candidate, no prefix matched"),
+                }
+            } else if filtered {
+                String::from("ignored, excluded by --include, --exclude or --only-prefixed")
+            } else if denied {
+                String::from("ignored, on the built-in denylist")
+            } else {
+                candidates.push(name);
+                match prefix {
+                    Some(p) => format!("candidate {}, renamed by prefix `{}`", candidates.len(), p),
+                    None => format!("candidate {}, no prefix matched", candidates.len()),
+                }
+            };
+            lines.push(what + &status);
+        }
+
+        let resolution = match (candidates.first(), entry) {
+            _ if matches!(self.mode, Mode::Left | Mode::Inner) && entry.is_none() => {
+                String::from("not in the output, the template has no entry for it")
+            }
+            (None, Some(_)) if self.mode == Mode::Inner => {
+                String::from("not in the output, no candidate in the environment")
+            }
+            (None, Some(_)) => String::from("the template default, no candidate in the environment"),
+            (None, None) => String::from("not in the output, no candidate in the environment"),
+            (Some(_), _) if self.mode == Mode::Plain && candidates.len() > 1 => format!(
+                "every candidate is written, {} times; readers usually keep the last one",
+                candidates.len()
+            ),
+            (Some(name), _) if candidates.len() > 1 => format!(
+                "env {}, the first of {} candidates in the order of the environment",
+                name.to_string_lossy(),
+                candidates.len()
+            ),
+            (Some(name), Some(_)) => format!("env {}, which overrides the template default", name.to_string_lossy()),
+            (Some(name), None) => format!("env {}", name.to_string_lossy()),
+        };
+        lines.push(format!("  resolved: {}", resolution));
+        if let Some(value) = value {
+            lines.push(format!("  value: `{}`", mask(value)));
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::Pattern;
+    use crate::template::Annotation;
+    use crate::tests::to_os_str;
+
+    #[test]
+    fn test_trace() {
+        let entries = vec![Entry {
+            key: "DB_HOST".into(),
+            value: "localhost".into(),
+            line: 4,
+            annotations: vec![Annotation { name: "required".into(), argument: "".into(), line: 3 }],
+        }];
+        let env = to_os_str(vec![
+            ("STAGING_DB_HOST", "staging"),
+            ("DB_HOST", "local"),
+            ("ST_DB_HOST", "hidden"),
+            ("PATH", "/bin"),
+        ]);
+        let prefixes = vec![String::from("ST"), String::from("STAGING_"), String::from("ST_")];
+        let filter = Filter::default();
+        let explain = Explain {
+            key: OsStr::new("DB_HOST"),
+            mode: Mode::Left,
+            template: Some(".env.tpl"),
+            entries: &entries,
+            env: &env,
+            prefixes: &prefixes,
+            filter: &filter,
+            denylist: None,
+        };
+        let lines = explain.trace(Some(OsStr::new("local")), |v| v.to_string_lossy().into_owned());
+        assert_eq!(lines, vec![
+            "DB_HOST",
+            "  template .env.tpl line 4: default `localhost`, @required",
+            "  prefixes, first match wins: ST, STAGING_, ST_",
+            "  env STAGING_DB_HOST=`staging`: not a candidate, prefix `ST` matched first and renamed it to AGING_DB_HOST",
+            "  env DB_HOST=`local`: candidate 1, no prefix matched",
+            "  env ST_DB_HOST=`hidden`: not a candidate, prefix `ST` matched first and renamed it to _DB_HOST",
+            "  resolved: env DB_HOST, which overrides the template default",
+            "  value: `local`",
+        ]);
+    }
+
+    #[test]
+    fn test_trace_literal_dollar() {
+        // `X='$y'` in a template.
+        let entries = vec![Entry { key: "X".into(), value: "$$y".into(), line: 1, annotations: vec![] }];
+        let filter = Filter::default();
+        let explain = Explain {
+            key: OsStr::new("X"),
+            mode: Mode::Left,
+            template: Some(".env.tpl"),
+            entries: &entries,
+            env: &[],
+            prefixes: &[],
+            filter: &filter,
+            denylist: None,
+        };
+        let lines = explain.trace(Some(OsStr::new("$y")), |v| v.to_string_lossy().into_owned());
+        assert_eq!(lines, vec![
+            "X",
+            "  template .env.tpl line 1: default `$y`",
+            "  resolved: the template default, no candidate in the environment",
+            "  value: `$y`",
+        ]);
+    }
+
+    #[test]
+    fn test_trace_hidden() {
+        let env = to_os_str(vec![
+            ("GITHUB_TOKEN", "ghp_supersecret"),
+            ("APP_GITHUB_TOKEN", "ghp_prefixed"),
+            ("STAGING_GITHUB_TOKEN", "ghp_excluded"),
+        ]);
+        let prefixes = vec![String::from("APP_"), String::from("STAGING_")];
+        let filter = Filter { exclude: vec![Pattern::parse("STAGING_*").unwrap()], ..Filter::default() };
+        let denylist = Denylist::builtin();
+        let explain = Explain {
+            key: OsStr::new("GITHUB_TOKEN"),
+            mode: Mode::Plain,
+            template: None,
+            entries: &[],
+            env: &env,
+            prefixes: &prefixes,
+            filter: &filter,
+            denylist: Some(&denylist),
+        };
+        let lines = explain.trace(None, |v| v.to_string_lossy().into_owned());
+        assert!(lines.iter().all(|l| !l.contains("ghp_")), "{:?}", lines);
+        assert_eq!(lines[3..6], [
+            "  env GITHUB_TOKEN=<hidden>: ignored, on the built-in denylist",
+            "  env APP_GITHUB_TOKEN=<hidden>: ignored, on the built-in denylist",
+            "  env STAGING_GITHUB_TOKEN=<hidden>: ignored, excluded by --include, --exclude or --only-prefixed",
+        ]);
+    }
+}
@@ -0,0 +1,336 @@
+//! Atomic writes of the output file.
+//!
+//! The output is written to a temporary file in the same directory, synced to
+//! disk and renamed over the target, so readers see either the old or the new
+//! file and never a partial one. A file that already has the new contents is
+//! left alone, so its modification time only changes when its contents do.
+//! Only its mode is corrected.
+
+use std::collections::hash_map::RandomState;
+use std::fs::{self, File, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use eyre::Result;
+
+use crate::Error;
+
+#[derive(Debug, Clone)]
+pub struct FileOptions {
+    /// Permissions of the written file, on unix.
+    pub mode: u32,
+    /// Fail instead of replacing an existing file.
+    pub no_clobber: bool,
+    /// Keep the previous file next to the new one, with a timestamp suffix.
+    pub backup: bool,
+}
+
+/// Parse an octal file mode like `600`.
+pub fn parse_mode(s: &str) -> Result<u32, String> {
+    match u32::from_str_radix(s, 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(mode),
+        _ => Err(format!("expected an octal file mode like 600, got `{}`", s)),
+    }
+}
+
+/// Atomically replace `path` with `contents`.
+/// Returns whether the file changed.
+pub fn write(path: &Path, contents: &[u8], options: &FileOptions) -> Result<bool> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let file_name = path.file_name().ok_or_else(|| Error::InvalidOutput { path: path.to_path_buf() })?;
+    if is_unchanged(path, contents)? {
+        fix_mode(path, options.mode)?;
+        return Ok(false);
+    }
+
+    let (tmp, file) = create_tmp(dir, &file_name.to_string_lossy(), options.mode)?;
+    // From here on the temporary file is ours to clean up.
+    let result = write_tmp(file, contents, options).and_then(|_| replace(&tmp, path, options));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result?;
+
+    sync_dir(dir)?;
+    Ok(true)
+}
+
+/// Whether `path` exists and already holds exactly `contents`.
+fn is_unchanged(path: &Path, contents: &[u8]) -> io::Result<bool> {
+    match fs::metadata(path) {
+        Ok(metadata) if metadata.is_file() && metadata.len() == contents.len() as u64 => {
+            Ok(fs::read(path)? == contents)
+        }
+        Ok(_) => Ok(false),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Create a new temporary file in `dir`, under a random name that is tried
+/// again when it is taken, for example by a run that was killed.
+fn create_tmp(dir: &Path, file_name: &str, mode: u32) -> io::Result<(PathBuf, File)> {
+    let mut attempts = 0;
+    loop {
+        let tmp = dir.join(format!(".{}.{:016x}.tmp", file_name, random()));
+        match create(&tmp, mode) {
+            Ok(file) => return Ok((tmp, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempts < 16 => attempts += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn write_tmp(mut file: File, contents: &[u8], options: &FileOptions) -> Result<()> {
+    set_mode(&file, options.mode)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn create(path: &Path, mode: u32) -> io::Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    OpenOptions::new().write(true).create_new(true).mode(mode).open(path)
+}
+
+#[cfg(not(unix))]
+fn create(path: &Path, _mode: u32) -> io::Result<File> {
+    OpenOptions::new().write(true).create_new(true).open(path)
+}
+
+/// The mode given to open is reduced by the umask, so set it again.
+#[cfg(unix)]
+fn set_mode(file: &File, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    file.set_permissions(fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_file: &File, _mode: u32) -> io::Result<()> {
+    Ok(())
+}
+
+/// Set the mode of an existing file, when it differs. This does not change
+/// its modification time.
+#[cfg(unix)]
+fn fix_mode(path: &Path, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let permissions = fs::metadata(path)?.permissions();
+    if permissions.mode() & 0o7777 != mode {
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn fix_mode(_path: &Path, _mode: u32) -> io::Result<()> {
+    Ok(())
+}
+
+/// A random number, seeded by the hasher keys std draws from the OS.
+fn random() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(process::id());
+    if let Ok(d) = SystemTime::now().duration_since(UNIX_EPOCH) {
+        hasher.write_u128(d.as_nanos());
+    }
+    hasher.finish()
+}
+
+fn replace(tmp: &Path, path: &Path, options: &FileOptions) -> Result<()> {
+    if options.no_clobber {
+        // Linking fails when `path` exists, without the race of checking first.
+        return match fs::hard_link(tmp, path) {
+            Ok(()) => Ok(fs::remove_file(tmp)?),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                Err(Error::OutputExists { path: path.to_path_buf() }.into())
+            }
+            Err(e) => Err(e.into()),
+        };
+    }
+    if options.backup && path.exists() {
+        fs::copy(path, backup_path(path, SystemTime::now()))?;
+    }
+    fs::rename(tmp, path)?;
+    Ok(())
+}
+
+/// Append `contents` to `path`, creating it if needed. For files that others
+/// read line by line as they grow, like `$GITHUB_ENV`, where replacing the
+/// file would lose what is already there.
+pub fn append(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(contents)?;
+    Ok(())
+}
+
+/// Make the rename durable.
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// `path` with a `.YYYYMMDDTHHMMSSZ.bak` suffix.
+fn backup_path(path: &Path, now: SystemTime) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}.bak", timestamp(now)));
+    PathBuf::from(name)
+}
+
+/// A UTC timestamp like `20261018T093000Z`.
+fn timestamp(t: SystemTime) -> String {
+    let secs = t.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (days, rem) = (secs / 86400, secs % 86400);
+
+    // Civil date from days since the epoch, after Howard Hinnant's algorithm.
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dump-env-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn options() -> FileOptions {
+        FileOptions { mode: 0o600, no_clobber: false, backup: false }
+    }
+
+    #[test]
+    fn test_timestamp() {
+        assert_eq!(timestamp(UNIX_EPOCH), "19700101T000000Z");
+        let t = UNIX_EPOCH + Duration::from_secs(1_709_210_096);
+        assert_eq!(timestamp(t), "20240229T123456Z");
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(parse_mode("600"), Ok(0o600));
+        assert_eq!(parse_mode("0644"), Ok(0o644));
+        assert!(parse_mode("800").is_err());
+    }
+
+    #[test]
+    fn test_write() {
+        let dir = test_dir("write");
+        let path = dir.join(".env");
+        assert!(write(&path, b"A=1\n", &options()).unwrap());
+        assert!(write(&path, b"A=2\n", &options()).unwrap());
+        assert_eq!(fs::read(&path).unwrap(), b"A=2\n");
+        // Only the target is left behind.
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_unchanged() {
+        let dir = test_dir("unchanged");
+        let path = dir.join(".env");
+        let options = FileOptions { backup: true, ..options() };
+        assert!(write(&path, b"A=1\n", &options).unwrap());
+        let modified = fs::metadata(&path).unwrap().modified().unwrap();
+        assert!(!write(&path, b"A=1\n", &options).unwrap());
+        assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), modified);
+        // No backup of an unchanged file.
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+            assert!(!write(&path, b"A=1\n", &options).unwrap());
+            let metadata = fs::metadata(&path).unwrap();
+            assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+            assert_eq!(metadata.modified().unwrap(), modified);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_stale_tmp() {
+        let dir = test_dir("stale-tmp");
+        let path = dir.join(".env");
+        // Left behind by a killed run, in the old naming scheme.
+        let stale = dir.join(format!(".env.{}.tmp", process::id()));
+        fs::write(&stale, b"x").unwrap();
+        write(&path, b"A=1\n", &options()).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"A=1\n");
+        assert_eq!(fs::read(&stale).unwrap(), b"x");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_no_clobber() {
+        let dir = test_dir("no-clobber");
+        let path = dir.join(".env");
+        let options = FileOptions { no_clobber: true, ..options() };
+        write(&path, b"A=1\n", &options).unwrap();
+        assert!(!write(&path, b"A=1\n", &options).unwrap());
+        let result = write(&path, b"A=2\n", &options).unwrap_err();
+        assert!(matches!(result.downcast_ref::<Error>(), Some(Error::OutputExists { .. })));
+        assert_eq!(fs::read(&path).unwrap(), b"A=1\n");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_backup() {
+        let dir = test_dir("backup");
+        let path = dir.join(".env");
+        let options = FileOptions { backup: true, ..options() };
+        write(&path, b"A=1\n", &options).unwrap();
+        write(&path, b"A=2\n", &options).unwrap();
+        let backups: Vec<PathBuf> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .filter(|p| p.to_string_lossy().ends_with(".bak"))
+            .collect();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(fs::read(&backups[0]).unwrap(), b"A=1\n");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,111 @@
+//! Selection of the environment variables that take part in a dump.
+//!
+//! Patterns are globs where `*` matches any run of characters and `?` a single
+//! one, or regular expressions when prefixed with `re:`. Both match the whole
+//! variable name as it is in the environment, before prefixes are stripped.
+
+use regex::bytes::Regex;
+
+use crate::{bytes, EnvItems};
+
+#[derive(Debug, Clone)]
+pub struct Pattern(Regex);
+
+impl Pattern {
+    /// Parse a `--include` or `--exclude` pattern.
+    pub fn parse(s: &str) -> Result<Pattern, String> {
+        let re = match s.strip_prefix("re:") {
+            Some(re) => re.to_string(),
+            None => glob(s),
+        };
+        Regex::new(&re).map(Pattern).map_err(|e| format!("invalid pattern `{}`: {}", s, e))
+    }
+
+    pub fn is_match(&self, key: &[u8]) -> bool {
+        self.0.is_match(key)
+    }
+}
+
+/// An anchored regular expression for a glob.
+fn glob(s: &str) -> String {
+    let mut re = String::from("^");
+    let mut literal = String::new();
+    for c in s.chars() {
+        let wildcard = match c {
+            '*' => "(?s-u:.)*",
+            '?' => "(?s-u:.)",
+            _ => {
+                literal.push(c);
+                continue;
+            }
+        };
+        re.push_str(&regex::escape(&literal));
+        re.push_str(wildcard);
+        literal.clear();
+    }
+    re.push_str(&regex::escape(&literal));
+    re.push('$');
+    re
+}
+
+#[derive(Debug, Default)]
+pub struct Filter {
+    /// When not empty, a variable must match one of these.
+    pub include: Vec<Pattern>,
+    /// A variable matching one of these is dropped.
+    pub exclude: Vec<Pattern>,
+    /// When not empty, a variable must start with one of these.
+    pub prefixes: Vec<String>,
+}
+
+impl Filter {
+    pub fn is_match(&self, key: &[u8]) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| p.is_match(key)))
+            && !self.exclude.iter().any(|p| p.is_match(key))
+            && (self.prefixes.is_empty() || self.prefixes.iter().any(|p| key.starts_with(p.as_bytes())))
+    }
+
+    pub fn apply(&self, items: EnvItems) -> EnvItems {
+        items.into_iter().filter(|(k, _)| self.is_match(bytes::as_bytes(k))).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::to_os_str;
+
+    fn patterns(xs: &[&str]) -> Vec<Pattern> {
+        xs.iter().map(|x| Pattern::parse(x).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_pattern() {
+        let p = Pattern::parse("APP_*").unwrap();
+        assert!(p.is_match(b"APP_"));
+        assert!(p.is_match(b"APP_DB_HOST"));
+        assert!(p.is_match(b"APP_\xff"));
+        assert!(!p.is_match(b"MY_APP_DB_HOST"));
+        let p = Pattern::parse("A?.B").unwrap();
+        assert!(p.is_match(b"AX.B"));
+        assert!(!p.is_match(b"AXXB"));
+        let p = Pattern::parse("re:_(TOKEN|KEY)$").unwrap();
+        assert!(p.is_match(b"GITHUB_TOKEN"));
+        assert!(!p.is_match(b"TOKEN_COUNT"));
+        assert!(Pattern::parse("re:(").is_err());
+    }
+
+    #[test]
+    fn test_apply() {
+        let items = to_os_str(vec![("APP_A", "1"), ("APP_TOKEN", "2"), ("PATH", "3"), ("STAGING_APP_B", "4")]);
+        let filter = Filter {
+            include: patterns(&["APP_*", "STAGING_*"]),
+            exclude: patterns(&["*_TOKEN"]),
+            prefixes: vec![],
+        };
+        assert_eq!(filter.apply(items.clone()), to_os_str(vec![("APP_A", "1"), ("STAGING_APP_B", "4")]));
+
+        let filter = Filter { prefixes: vec![String::from("STAGING_")], ..Filter::default() };
+        assert_eq!(filter.apply(items), to_os_str(vec![("STAGING_APP_B", "4")]));
+    }
+}
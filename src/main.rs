@@ -7,36 +7,309 @@
 //!
 //! This tool is helpful in CI pipelines where you can store environment vars as part of the pipeline
 //! and need a proper way to generate .env files.
+//!
+//! ## Usage
+//!
+//! * `dump-env dump` prints the environment.
+//! * `dump-env merge [--mode left|full|inner] TEMPLATE` merges a template, the left input, with
+//!   the environment, the right input.
+//! * `dump-env check TEMPLATE` merges and validates without printing.
+//! * `dump-env diff TEMPLATE` shows how the environment differs from the template.
+//! * `dump-env exec TEMPLATE -- COMMAND` runs a command with the merged environment, without
+//!   the variables that filters or the denylist drop.
+//! * `dump-env explain KEY [TEMPLATE]` traces how a key resolves.
+//!
+//! Without a subcommand, `--source TEMPLATE` is `merge --mode left` and `--template TEMPLATE`
+//! is `merge --mode full`. Options can be given before or after the subcommand.
+
+mod bytes;
+mod denylist;
+mod diff;
+mod diagnostic;
+mod dotenv;
+mod explain;
+mod file;
+mod filter;
+mod interpolate;
+mod output;
+mod provenance;
+mod redact;
+mod scan;
+mod schema;
+mod secret;
+mod systemd;
+mod template;
 
 use std::env;
-use std::ffi::OsString;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
-use clap::Parser;
+use std::process;
+use clap::{ArgEnum, Parser, Subcommand};
 use thiserror::Error;
 use eyre::Result;
+use denylist::Denylist;
+use diagnostic::ParseError;
+use dotenv::Dialect;
+use explain::Explain;
+use file::FileOptions;
+use filter::Filter;
+use output::{Format, InvalidUtf8, Manifest};
+use provenance::{Origin, Sourced};
+use redact::{Redact, Redactor, Sensitive};
+use secret::Secrets;
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Args {
-    /// Source template file
-    #[clap(short, long)]
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    /// Same as `merge --mode left SOURCE`
+    #[clap(short, long, allow_hyphen_values = true, global = true, conflicts_with = "template")]
     source: Option<String>,
 
-    /// Template file
-    #[clap(short, long)]
+    /// Same as `merge --mode full TEMPLATE`
+    #[clap(short, long, allow_hyphen_values = true, global = true)]
     template: Option<String>,
 
+    /// Template file format
+    #[clap(long, arg_enum, default_value = "dotenv", global = true)]
+    input_format: Dialect,
+
     /// Prefixes
-    #[clap(short, long)]
-    prefixes: Vec<String>
+    #[clap(short, long, global = true)]
+    prefixes: Vec<String>,
+
+    /// Only use environment variables matching this glob, or regex when prefixed with `re:`
+    #[clap(long, parse(try_from_str = filter::Pattern::parse), global = true)]
+    include: Vec<filter::Pattern>,
+
+    /// Ignore environment variables matching this glob, or regex when prefixed with `re:`
+    #[clap(long, parse(try_from_str = filter::Pattern::parse), global = true)]
+    exclude: Vec<filter::Pattern>,
+
+    /// Ignore environment variables that do not start with one of the prefixes
+    #[clap(long, requires = "prefixes", global = true)]
+    only_prefixed: bool,
+
+    /// Keep well-known CI and cloud credentials that are dropped from full dumps by default
+    #[clap(long, global = true)]
+    no_denylist: bool,
+
+    /// Output format
+    #[clap(short, long, arg_enum, default_value = "dotenv", global = true)]
+    format: Format,
+
+    /// Shorthand for `--format sh`
+    #[clap(short, long, conflicts_with = "format", global = true)]
+    export: bool,
+
+    /// Fail on template lines that cannot be parsed instead of skipping them with a warning
+    #[clap(long, global = true)]
+    strict: bool,
+
+    /// Treat template keys without a default value as required
+    #[clap(long, global = true)]
+    require_values: bool,
+
+    /// What to do with keys and values that are not valid UTF-8
+    #[clap(long, arg_enum, default_value = "keep", global = true)]
+    invalid_utf8: InvalidUtf8,
+
+    /// Treat keys with this prefix as secret, in addition to keys annotated with `@secret`
+    #[clap(long, global = true)]
+    secret_prefix: Vec<String>,
+
+    /// Mask the values of secret keys and keys like `*_TOKEN`, `*_PASSWORD` or `*_KEY`
+    #[clap(long, arg_enum, min_values = 0, require_equals = true, default_missing_value = "stars", global = true)]
+    redact: Option<Redact>,
+
+    /// Note where each value came from, in a comment above each entry or in JSON fields
+    #[clap(long, global = true)]
+    annotate: bool,
+
+    /// Warn about values of keys that are not secret but look like secrets
+    #[clap(long, global = true)]
+    scan_secrets: bool,
+
+    /// Fail on values of keys that are not secret but look like secrets
+    #[clap(long, global = true)]
+    deny_secrets: bool,
+
+    /// Kubernetes manifest name
+    #[clap(long, default_value = "env", global = true)]
+    name: String,
+
+    /// Kubernetes manifest namespace
+    #[clap(long, global = true)]
+    namespace: Option<String>,
+
+    /// Kubernetes manifest label as `key=value`
+    #[clap(long = "label", parse(try_from_str = output::parse_label), global = true)]
+    labels: Vec<(String, String)>,
+
+    /// Write Kubernetes Secret values to `stringData` instead of base64 encoded `data`
+    #[clap(long, global = true)]
+    string_data: bool,
+
+    /// In `k8s-env` output, refer to secret keys in the Secret named by `--name` instead of writing their values
+    #[clap(long, global = true)]
+    secret_key_ref: bool,
+
+    /// Number of variables the GitLab instance accepts from a dotenv report
+    #[clap(long, default_value = "50", global = true)]
+    gitlab_max_variables: usize,
+
+    /// Write the output to this file, atomically, instead of stdout. The GitHub formats append to it
+    #[clap(short, long, global = true)]
+    output: Option<PathBuf>,
+
+    /// Permissions of the output file, in octal
+    #[clap(long, default_value = "600", parse(try_from_str = file::parse_mode), requires = "output", global = true)]
+    file_mode: u32,
+
+    /// Fail when the output file already exists
+    #[clap(long, requires = "output", global = true)]
+    no_clobber: bool,
+
+    /// Keep the previous output file with a timestamp suffix
+    #[clap(long, requires = "output", conflicts_with = "no-clobber", global = true)]
+    backup: bool,
+
+    /// Exit with status 2 when the output file changed, and 0 when it was left as is
+    #[clap(long, requires = "output", global = true)]
+    exit_code: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the environment
+    Dump,
+
+    /// Merge a template with the environment and print the result
+    Merge(Merge),
+
+    /// Merge a template with the environment and validate the result, without printing it
+    Check(Merge),
+
+    /// Show how the environment differs from a template
+    Diff {
+        /// Template file, optional when prefixed with `-` after `--`
+        #[clap(name = "template-file", value_name = "TEMPLATE")]
+        template: String,
+    },
+
+    /// Run a command with the merged environment on top of the current one, less the
+    /// variables dropped by `--include`, `--exclude`, `--only-prefixed` and the denylist
+    Exec {
+        /// Template file. Without it the command gets the filtered environment, and prefixed
+        /// variables also under their names without the prefix
+        #[clap(name = "template-file", value_name = "TEMPLATE")]
+        template: Option<String>,
+
+        /// Which keys to keep
+        #[clap(long, arg_enum, default_value = "left")]
+        mode: Mode,
+
+        /// The command and its arguments
+        #[clap(required = true, last = true)]
+        command: Vec<OsString>,
+    },
+
+    /// Show how KEY resolves: the template entry, every environment variable that could
+    /// provide it, the prefix that renamed each of them, and which one won
+    Explain {
+        key: String,
+
+        /// Template file, optional when prefixed with `-` after `--`
+        #[clap(name = "template-file", value_name = "TEMPLATE")]
+        template: Option<String>,
+
+        /// Which keys to keep
+        #[clap(long, arg_enum, default_value = "left")]
+        mode: Mode,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+struct Merge {
+    /// Template file, the left input, optional when prefixed with `-` after `--`. The environment is the right input
+    #[clap(name = "template-file", value_name = "TEMPLATE")]
+    template: String,
+
+    /// Which keys to keep
+    #[clap(long, arg_enum, default_value = "left")]
+    mode: Mode,
+}
+
+/// How the template and the environment are combined.
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    /// The keys of the template
+    Left,
+    /// The keys of the template and of the environment
+    Full,
+    /// The keys of the template that are set in the environment
+    Inner,
+    /// Only the environment
+    #[clap(skip)]
+    Plain,
 }
 
 #[derive(Debug, Error)]
 enum Error {
-    #[error("Template not found")]
-    TemplateNotFound
+    #[error("Template not found: {}", .path.display())]
+    TemplateNotFound { path: PathBuf },
+
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+
+    #[error("{count} problem(s) found in template {}", .path.display())]
+    Strict { path: PathBuf, count: usize },
+
+    #[error("Invalid substitution `{expr}`")]
+    InvalidSubstitution { expr: String },
+
+    #[error("Variable `{name}` is not set: {message}")]
+    RequiredVariable { name: String, message: String },
+
+    #[error("Variable reference cycle: {chain}")]
+    InterpolationCycle { chain: String },
+
+    #[error("`{key}` contains bytes that are not valid UTF-8")]
+    InvalidUtf8 { key: String },
+
+    #[error("Invalid key `{key}`: {reason}")]
+    InvalidKey { key: String, reason: String },
+
+    #[error("Cannot write {format} output:\n  {}", .problems.join("\n  "))]
+    Unrepresentable { format: &'static str, problems: Vec<String> },
+
+    #[error("Values that look like secrets found under keys that are not secret:\n  {}", .findings.join("\n  "))]
+    SecretsFound { findings: Vec<String> },
+
+    #[error("{message}")]
+    ConflictingArgs { message: String },
+
+    #[error("Cannot run {program}")]
+    Exec { program: String, source: io::Error },
+
+    #[error("--annotate is not supported by the {format} output format")]
+    AnnotateUnsupported { format: String },
+
+    #[error("Invalid output file: {}", .path.display())]
+    InvalidOutput { path: PathBuf },
+
+    #[error("Output file already exists: {}", .path.display())]
+    OutputExists { path: PathBuf },
+
+    #[error("Missing required variables: {}", .keys.join(", "))]
+    MissingRequired { keys: Vec<String> },
+
+    #[error("Invalid values:\n  {}", .violations.join("\n  "))]
+    Validation { violations: Vec<String> },
 }
 
 type EnvItem = (OsString, OsString);
@@ -44,53 +317,275 @@ type EnvItems = Vec<EnvItem>;
 
 fn main() -> Result<()> {
     let args = Args::parse();
-
-    if let Some(source_path) = args.source {
-        let path = PathBuf::from(&source_path);
-        print(left_join(parse_template(&path)?, get_env(&args.prefixes)));
-        return Ok(());
+    let file = args.output.clone().map(|path| {
+        let file_options = FileOptions {
+            mode: args.file_mode,
+            no_clobber: args.no_clobber,
+            backup: args.backup,
+        };
+        (path, file_options)
+    });
+    let filter = Filter {
+        include: args.include.clone(),
+        exclude: args.exclude.clone(),
+        prefixes: if args.only_prefixed { args.prefixes.clone() } else { Vec::new() },
+    };
+
+    let (mode, template_path) = input(&args)?;
+    let entries = match template_path {
+        Some(path) => parse_template(path, args.input_format, args.strict)?,
+        None => Vec::new(),
+    };
+    let keys: Vec<OsString> = entries.iter().map(|e| e.key.clone()).collect();
+    let denylist = (!args.no_denylist && matches!(mode, Mode::Full | Mode::Plain)).then(Denylist::builtin);
+
+    let sourced_env = get_env(&args.prefixes, &filter);
+    let sourced_env = match &denylist {
+        Some(denylist) => deny(denylist, sourced_env, &keys),
+        None => sourced_env,
+    };
+    let (items, provenance) = match mode {
+        Mode::Left => provenance::split(left_join(provenance::from_template(&entries), sourced_env.clone())),
+        Mode::Full => provenance::split(full_join(provenance::from_template(&entries), sourced_env.clone())),
+        Mode::Inner => provenance::split(inner_join(provenance::from_template(&entries), sourced_env.clone())),
+        Mode::Plain => provenance::split(sourced_env.clone()),
+    };
+    // What `exec` passes on besides the merged items: the variables that passed the
+    // filters and the denylist, under their own names.
+    let kept: EnvItems = sourced_env
+        .iter()
+        .filter_map(|(_, (v, origin))| match origin {
+            Origin::Env { name } => Some((name.clone(), v.clone())),
+            Origin::Template { .. } => None,
+        })
+        .collect();
+    let (env_items, _) = provenance::split(sourced_env);
+    let items = match mode {
+        Mode::Plain => items,
+        _ => interpolate::expand(items, &env_items)?,
+    };
+    let mut options = output::Options {
+        format: if args.export { Format::Sh } else { args.format },
+        invalid_utf8: args.invalid_utf8,
+        secrets: Secrets::new(&args.secret_prefix, &entries),
+        manifest: Manifest {
+            name: args.name.clone(),
+            namespace: args.namespace.clone(),
+            labels: args.labels.clone(),
+            string_data: args.string_data,
+            secret_key_ref: args.secret_key_ref,
+        },
+        gitlab_max_variables: args.gitlab_max_variables,
+        redact: args.redact.map(Redactor::new),
+        provenance: None,
+    };
+    let mask = |k: &OsStr, v: &OsStr| match &options.redact {
+        Some(redactor) => redactor.value(k, v, &options.secrets).to_string_lossy().into_owned(),
+        None => v.to_string_lossy().into_owned(),
+    };
+
+    match &args.command {
+        Some(Command::Explain { key, .. }) => {
+            let key = OsString::from(key);
+            let explain = Explain {
+                key: &key,
+                mode,
+                template: template_path,
+                entries: &entries,
+                env: &env::vars_os().collect::<EnvItems>(),
+                prefixes: &args.prefixes,
+                filter: &filter,
+                denylist: denylist.as_ref(),
+            };
+            let value = items.iter().find(|(k, _)| k == &key).map(|(_, v)| v.as_os_str());
+            for line in explain.trace(value, |v| mask(&key, v)) {
+                println!("{}", line);
+            }
+            return Ok(());
+        }
+        Some(Command::Diff { .. }) => {
+            for line in diff::lines(&entries, &items, &provenance, mask) {
+                println!("{}", line);
+            }
+            return Ok(());
+        }
+        _ => {}
     }
 
-    if let Some(template_path) = args.template {
-        let path = PathBuf::from(&template_path);
-        print(full_join(parse_template(&path)?, get_env(&args.prefixes)));
-        return Ok(());
+    template::check_required(&entries, &items, args.require_values)?;
+    let sensitive = Sensitive::builtin();
+    schema::validate(&entries, &items, |k, v| match &options.redact {
+        Some(_) => format!("`{}`", mask(k, v)),
+        None if sensitive.is_match(k, &options.secrets) => String::from("the secret value"),
+        None => format!("`{}`", v.to_string_lossy()),
+    })?;
+    scan_secrets(&items, &options.secrets, args.scan_secrets, args.deny_secrets)?;
+    match &args.command {
+        Some(Command::Check(merge)) => {
+            println!("{}: ok, {} keys", merge.template, items.len());
+            Ok(())
+        }
+        Some(Command::Exec { command, .. }) => exec(command, kept, items),
+        _ => {
+            if args.annotate {
+                options.provenance = Some(provenance);
+            }
+            let changed = print(items, &options, file.as_ref())?;
+            exit(changed, args.exit_code)
+        }
+    }
+}
 
+/// The template and how to merge it with the environment, from the subcommand
+/// or from `--source` and `--template`. A subcommand that names its own
+/// template cannot be combined with those flags.
+fn input(args: &Args) -> Result<(Mode, Option<&str>), Error> {
+    let flags = match (&args.source, &args.template) {
+        (Some(path), _) => Some((Mode::Left, path.as_str())),
+        (None, Some(path)) => Some((Mode::Full, path.as_str())),
+        (None, None) => None,
+    };
+    let (name, input) = match &args.command {
+        None => return Ok(flags.map_or((Mode::Plain, None), |(mode, path)| (mode, Some(path)))),
+        Some(Command::Dump) => ("dump", (Mode::Plain, None)),
+        Some(Command::Merge(merge)) => ("merge", (merge.mode, Some(merge.template.as_str()))),
+        Some(Command::Check(merge)) => ("check", (merge.mode, Some(merge.template.as_str()))),
+        Some(Command::Diff { template }) => ("diff", (Mode::Full, Some(template.as_str()))),
+        Some(Command::Exec { template, mode, .. }) => match template {
+            Some(path) => ("exec", (*mode, Some(path.as_str()))),
+            None => ("exec", (Mode::Plain, None)),
+        },
+        // `explain KEY --source TEMPLATE` predates the template argument.
+        Some(Command::Explain { template, mode, .. }) => match (template, flags) {
+            (Some(path), _) => ("explain", (*mode, Some(path.as_str()))),
+            (None, Some((mode, path))) => return Ok((mode, Some(path))),
+            (None, None) => ("explain", (Mode::Plain, None)),
+        },
+    };
+    if flags.is_some() {
+        let message = match name {
+            "dump" => String::from("--source and --template cannot be used with `dump`, use `merge` instead"),
+            _ => format!("--source and --template cannot be used with `{}`, it takes the template as argument", name),
+        };
+        return Err(Error::ConflictingArgs { message });
     }
+    Ok(input)
+}
 
-    print( get_env(&args.prefixes));
+/// Run `command` with `items` added to `kept`, the part of the environment that
+/// passed the filters and the denylist. On unix the process is replaced,
+/// elsewhere the exit status of the command is passed on.
+fn exec(command: &[OsString], kept: EnvItems, items: EnvItems) -> Result<()> {
+    let program = &command[0];
+    let mut cmd = process::Command::new(program);
+    cmd.args(&command[1..]).env_clear().envs(kept).envs(items);
+
+    #[cfg(unix)]
+    let source = {
+        use std::os::unix::process::CommandExt;
+        cmd.exec()
+    };
+    #[cfg(not(unix))]
+    let source = match cmd.status() {
+        Ok(status) => process::exit(status.code().unwrap_or(1)),
+        Err(e) => e,
+    };
+    Err(Error::Exec { program: program.to_string_lossy().into_owned(), source }.into())
+}
+
+/// With `--exit-code`, report through the exit status whether the output file changed.
+fn exit(changed: bool, exit_code: bool) -> Result<()> {
+    if exit_code && changed {
+        process::exit(2);
+    }
     Ok(())
 }
 
-fn strip_prefixes(prefixes: &[String], items: EnvItems) -> EnvItems {
+/// Drop the variables on the denylist, except `keep`, and say so on stderr.
+fn deny(denylist: &Denylist, env: Sourced, keep: &[OsString]) -> Sourced {
+    let (env, denied) = denylist.split(env, keep);
+    if !denied.is_empty() {
+        let names: Vec<_> = denied.iter().map(|k| k.to_string_lossy()).collect();
+        eprintln!(
+            "note: dropped {} variable(s) on the built-in denylist v{}: {}\n  use --no-denylist to keep them",
+            names.len(),
+            denylist::VERSION,
+            names.join(", ")
+        );
+    }
+    env
+}
+
+fn strip_prefixes<V>(prefixes: &[String], items: Vec<(OsString, V)>) -> Vec<(OsString, V)> {
     items.into_iter().map(|(k,v)| {
-        let key_string = k.to_string_lossy().to_string();
-        for pfx in prefixes {
-            // Return after the first prefix hit.
-            if let Some(x) = key_string.strip_prefix(pfx.as_str()) {
-                return (x.into(), v);
-            }
+        if let Some(pfx) = matching_prefix(prefixes, bytes::as_bytes(&k)) {
+            return (bytes::to_os_string(bytes::as_bytes(&k)[pfx.len()..].to_vec()), v);
         }
         (k, v)
     }).collect()
 }
 
-/// Prints a list of EnvItem to stdout.
-fn print(x: EnvItems) {
-    for (k, v) in x {
-        println!("{}={}", k.to_string_lossy(), v.to_string_lossy());
+/// The prefix that `strip_prefixes` removes from `key`: the first one it starts with.
+fn matching_prefix<'a>(prefixes: &'a [String], key: &[u8]) -> Option<&'a String> {
+    prefixes.iter().find(|pfx| key.starts_with(pfx.as_bytes()))
+}
+
+/// Look for values that seem to be secrets under keys that are not secret.
+/// Findings are warnings with `warn`, and an error with `deny`.
+fn scan_secrets(items: &[EnvItem], secrets: &Secrets, warn: bool, deny: bool) -> Result<(), Error> {
+    if !warn && !deny {
+        return Ok(());
+    }
+    let findings = scan::scan(items, secrets);
+    if deny && !findings.is_empty() {
+        return Err(Error::SecretsFound { findings });
+    }
+    for finding in &findings {
+        eprintln!("warning: {}", finding);
+    }
+    Ok(())
+}
+
+/// Prints a list of EnvItem to stdout, or atomically writes it to the output `file`.
+/// Formats that append, like `github-env`, append to `file` instead.
+/// Returns whether anything was written.
+fn print(x: EnvItems, options: &output::Options, file: Option<&(PathBuf, FileOptions)>) -> Result<bool> {
+    let preamble = output::preamble(&x, options)?;
+    let Some((path, file_options)) = file else {
+        // The preamble must not end up where stdout is redirected to.
+        io::stderr().lock().write_all(preamble.as_bytes())?;
+        output::write(&mut io::stdout().lock(), &x, options)?;
+        return Ok(true);
+    };
+    io::stdout().lock().write_all(preamble.as_bytes())?;
+
+    let mut contents = Vec::new();
+    output::write(&mut contents, &x, options)?;
+    if !options.format.appends() {
+        return file::write(path, &contents, file_options);
+    }
+    if file_options.no_clobber || file_options.backup {
+        let message = String::from("--no-clobber and --backup cannot be used with the GitHub formats, they append to the output file");
+        return Err(Error::ConflictingArgs { message }.into());
     }
+    file::append(path, &contents)?;
+    Ok(true)
 }
 
-/// Get environment vars as list of OsString tuples.
-fn get_env(prefixes: &[String]) -> EnvItems {
-    strip_prefixes(prefixes, env::vars_os().into_iter().map(|(k,v)| (k, v)).collect())
+/// Get environment vars as list of OsString tuples, with their original names as origin.
+/// The filter applies to the names before the prefixes are stripped.
+fn get_env(prefixes: &[String], filter: &Filter) -> Sourced {
+    let env = filter.apply(env::vars_os().collect()).into_iter().map(|(k, v)| {
+        let origin = Origin::Env { name: k.clone() };
+        (k, (v, origin))
+    });
+    strip_prefixes(prefixes, env.collect())
 }
 
 
 /// `left` is the template, `right` are the environment vars.
 /// Include all that is in `left` and overwrite with `right`.
-fn left_join(left: EnvItems, right: EnvItems) -> EnvItems {
+fn left_join<V: Clone>(left: Vec<(OsString, V)>, right: Vec<(OsString, V)>) -> Vec<(OsString, V)> {
     left.into_iter().map(|(lk, lv)| {
         for (rk, rv) in &right {
             if &lk == rk {
@@ -105,19 +600,25 @@ fn left_join(left: EnvItems, right: EnvItems) -> EnvItems {
 /// `left` is the template, `right` are the environment vars.
 /// Include all that is in `left` , overwrite with `right` extends results with
 /// missing keys from `right`.
-fn full_join(left: EnvItems, right: EnvItems) -> EnvItems {
+fn full_join<V: Clone>(left: Vec<(OsString, V)>, right: Vec<(OsString, V)>) -> Vec<(OsString, V)> {
     let mut x = left_join(left, right.clone());
     for (rk, rv) in &right {
         if !has_key(rk, &x) {
            x.push((rk.clone(), rv.clone()))
         }
     }
-    x.sort();
+    x.sort_by(|(a, _), (b, _)| a.cmp(b));
     x
 }
 
+/// `left` is the template, `right` are the environment vars.
+/// Include the keys of `left` that are in `right`, with the values of `right`.
+fn inner_join<V: Clone>(left: Vec<(OsString, V)>, right: Vec<(OsString, V)>) -> Vec<(OsString, V)> {
+    left_join(left, right.clone()).into_iter().filter(|(k, _)| has_key(k, &right)).collect()
+}
+
 /// Has key helper.
-fn has_key(key: &OsString, xs: &[EnvItem]) -> bool {
+fn has_key<V>(key: &OsString, xs: &[(OsString, V)]) -> bool {
     for (k, _v) in xs {
         if key == k {
             return true;
@@ -126,25 +627,37 @@ fn has_key(key: &OsString, xs: &[EnvItem]) -> bool {
     false
 }
 
-/// Parse a .env template file
-/// This trims whitespace and skips lines that start with #.
-fn parse_template(path: &Path) -> Result<EnvItems> {
+/// Parse a .env template file.
+/// See the `dotenv` and `systemd` modules for the supported grammars. As in
+/// systemd, a path prefixed with `-` may be missing. Problems with single
+/// lines are printed to stderr, or fail the parse when `strict` is set.
+fn parse_template(path: &str, dialect: Dialect, strict: bool) -> Result<Vec<template::Entry>> {
+    let (path, optional) = match path.strip_prefix('-') {
+        Some(p) if !p.is_empty() => (Path::new(p), true),
+        _ => (Path::new(path), false),
+    };
     if !path.exists() {
-        return Err(Error::TemplateNotFound.into());
-    }
-    let file = File::open(&path)?;
-
-    Ok(BufReader::new(file)
-        .lines()
-        .filter_map(|x| x.ok())
-        .filter(|x| !x.starts_with('#'))
-        .filter_map(|line| {
-            if let Some((left, right)) = line.split_once('=') {
-                Some((OsString::from(left.trim()), OsString::from(right.trim())))
-            } else {
-                None
-            }
-        }).collect())
+        if optional {
+            return Ok(Vec::new());
+        }
+        return Err(Error::TemplateNotFound { path: path.to_path_buf() }.into());
+    }
+    let contents = fs::read(path)?;
+
+    let parse = match dialect {
+        Dialect::Dotenv => dotenv::parse,
+        Dialect::Systemd => systemd::parse,
+    };
+    let (entries, warnings) = parse(&contents)
+        .map_err(|d| Error::from(ParseError::new(path, &contents, d)))?;
+    for d in &warnings {
+        let label = if strict { "error" } else { "warning" };
+        eprintln!("{}: {}\n", label, ParseError::new(path, &contents, d.clone()));
+    }
+    if strict && !warnings.is_empty() {
+        return Err(Error::Strict { path: path.to_path_buf(), count: warnings.len() }.into());
+    }
+    Ok(entries)
 }
 
 
@@ -153,7 +666,7 @@ mod tests {
 
     use super::*;
 
-    fn to_os_str(xs: Vec<(&str, &str)>) -> Vec<(OsString, OsString)> {
+    pub(crate) fn to_os_str(xs: Vec<(&str, &str)>) -> Vec<(OsString, OsString)> {
         xs.into_iter().map(|(k, v)|{
             (OsString::from(k), OsString::from(v))
         }).collect()
@@ -228,6 +741,16 @@ mod tests {
 
     }
 
+    #[test]
+    fn test_inner_join() {
+        let source = to_os_str(vec![("a", "1"), ("b", "2"), ("c", "3")]);
+        let env = to_os_str(vec![("c", "30"), ("d", "40"), ("a", "10")]);
+        let expect = to_os_str(vec![("a", "10"), ("c", "30")]);
+
+        let result = inner_join(source, env);
+        assert_eq!(result, expect);
+    }
+
     #[test]
     fn test_strip_prefixes() {
         let prefixes = vec![String::from("test_"), String::from("test2_")];
@@ -237,4 +760,23 @@ mod tests {
         let result = strip_prefixes(&prefixes, env);
         assert_eq!(result, expect);
     }
+
+    #[test]
+    fn test_options_after_subcommand() {
+        let args = Args::try_parse_from(["dump-env", "merge", "t.env", "--format", "json", "--annotate"]).unwrap();
+        assert_eq!(args.format, Format::Json);
+        assert!(args.annotate);
+
+        let args = Args::try_parse_from([
+            "dump-env", "merge", "t.env", "--mode", "full", "-o", "out.env", "--file-mode", "640", "--exit-code",
+        ])
+        .unwrap();
+        assert!(matches!(args.command, Some(Command::Merge(Merge { mode: Mode::Full, .. }))));
+        assert_eq!(args.output, Some(PathBuf::from("out.env")));
+        assert_eq!(args.file_mode, 0o640);
+        assert!(args.exit_code);
+
+        assert!(Args::try_parse_from(["dump-env", "merge", "t.env", "--exit-code"]).is_err());
+        assert!(Args::try_parse_from(["dump-env", "merge", "t.env", "--export", "--format", "json"]).is_err());
+    }
 }
@@ -0,0 +1,84 @@
+//! Files for `docker run --env-file`.
+//!
+//! Docker takes everything after the first `=` literally and has no quoting,
+//! so values are written unchanged. Items Docker would read back differently,
+//! or reject, are reported together instead of being written.
+
+use std::io::Write;
+
+use eyre::Result;
+
+use super::{comment, encode, Options};
+use crate::{EnvItem, Error};
+
+pub fn write(out: &mut dyn Write, items: &[EnvItem], options: &Options) -> Result<()> {
+    let mut lines = Vec::new();
+    let mut problems = Vec::new();
+    for (k, v) in items {
+        let key = encode(k, k, options.invalid_utf8)?;
+        let value = encode(v, k, options.invalid_utf8)?;
+        match problem(&key, &value) {
+            Some(reason) => problems.push(format!("{}: {}", k.to_string_lossy(), reason)),
+            None => lines.push([comment(k, options).as_bytes(), &key, b"=", &value, b"\n"].concat()),
+        }
+    }
+    if !problems.is_empty() {
+        return Err(Error::Unrepresentable { format: "docker", problems }.into());
+    }
+
+    for line in lines {
+        out.write_all(&line)?;
+    }
+    Ok(())
+}
+
+/// Why Docker cannot read back the item, if it cannot.
+fn problem(k: &[u8], v: &[u8]) -> Option<&'static str> {
+    let reason = if k.is_empty() {
+        "empty key"
+    } else if k.iter().any(u8::is_ascii_whitespace) {
+        "key contains whitespace"
+    } else if k[0] == b'#' {
+        "key starts with `#` and would be read as a comment"
+    } else if k.contains(&b'=') {
+        "key contains `=`"
+    } else if v.contains(&b'\n') {
+        "value contains a newline"
+    } else if v.ends_with(b"\r") {
+        "value ends with a carriage return"
+    } else if std::str::from_utf8(k).is_err() || std::str::from_utf8(v).is_err() {
+        "not valid UTF-8"
+    } else {
+        return None;
+    };
+    Some(reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{write_string, Format, Options};
+    use crate::tests::to_os_str;
+
+    #[test]
+    fn test_write() {
+        let items = to_os_str(vec![("A", " a \"b\" 'c' $d = e "), ("B", "")]);
+        let result = write_string(&items, &Options::new(Format::Docker)).unwrap();
+        assert_eq!(result, "A= a \"b\" 'c' $d = e \nB=\n");
+    }
+
+    #[test]
+    fn test_problems() {
+        let items = to_os_str(vec![("A", "x\ny"), ("B", "ok"), ("C D", "x"), ("#E", "x"), ("F", "x\r")]);
+        let result = write(&mut Vec::new(), &items, &Options::new(Format::Docker)).unwrap_err();
+        let Some(Error::Unrepresentable { problems, .. }) = result.downcast_ref::<Error>() else {
+            panic!("unexpected error {}", result);
+        };
+        assert_eq!(problems, &vec![
+            "A: value contains a newline",
+            "C D: key contains whitespace",
+            "#E: key starts with `#` and would be read as a comment",
+            "F: value ends with a carriage return",
+        ]);
+    }
+}
@@ -0,0 +1,107 @@
+//! `KEY=value` lines, as written by `dump-env` from the start.
+//!
+//! Values that the dotenv grammar would read differently are quoted: in single
+//! quotes when that is enough, otherwise in double quotes with the escapes of
+//! `dotenv::parse`.
+
+use std::io::Write;
+
+use eyre::Result;
+
+use super::{comment, encode, Options};
+use crate::EnvItem;
+
+pub fn write(out: &mut dyn Write, items: &[EnvItem], options: &Options) -> Result<()> {
+    for (k, v) in items {
+        let value = encode(v, k, options.invalid_utf8)?;
+        let key = encode(k, k, options.invalid_utf8)?;
+        out.write_all(comment(k, options).as_bytes())?;
+        out.write_all(&key)?;
+        out.write_all(b"=")?;
+        out.write_all(&quote(&value))?;
+        out.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// `value` as it has to be written to be read back as is.
+fn quote(value: &[u8]) -> Vec<u8> {
+    let multiline = value.iter().any(|b| matches!(b, b'\n' | b'\r'));
+    let quoted = value.starts_with(b"'") || value.starts_with(b"\"");
+    let trimmed = value.first().is_some_and(|b| b.is_ascii_whitespace())
+        || value.last().is_some_and(|b| b.is_ascii_whitespace());
+    let comment = value.windows(2).any(|w| matches!(w, b" #" | b"\t#"));
+    // `$` starts a reference for `dump-env` and docker-compose alike.
+    let reference = value.contains(&b'$');
+    if !(multiline || quoted || trimmed || comment || reference) {
+        return value.to_vec();
+    }
+    if !multiline && !value.contains(&b'\'') {
+        return [b"'", value, b"'"].concat();
+    }
+
+    let mut quoted = vec![b'"'];
+    for b in value {
+        match b {
+            b'\n' => quoted.extend_from_slice(b"\\n"),
+            b'\r' => quoted.extend_from_slice(b"\\r"),
+            b'\\' | b'"' | b'$' => quoted.extend_from_slice(&[b'\\', *b]),
+            b => quoted.push(*b),
+        }
+    }
+    quoted.push(b'"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::{write_string, Format, Options};
+    use crate::tests::to_os_str;
+    use crate::{dotenv, interpolate, template};
+
+    #[test]
+    fn test_write() {
+        let items = to_os_str(vec![
+            ("A", "a b"),
+            ("B", "a b # c"),
+            ("C", "$x"),
+            ("D", "it's $x"),
+            ("E", "-----BEGIN KEY-----\nabc\n-----END KEY-----"),
+            ("F", " \"q\" \\ "),
+            ("G", "x#y"),
+        ]);
+        let result = write_string(&items, &Options::new(Format::Dotenv)).unwrap();
+        assert_eq!(result, concat!(
+            "A=a b\n",
+            "B='a b # c'\n",
+            "C='$x'\n",
+            "D=\"it's \\$x\"\n",
+            "E=\"-----BEGIN KEY-----\\nabc\\n-----END KEY-----\"\n",
+            "F=' \"q\" \\ '\n",
+            "G=x#y\n",
+        ));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let items = to_os_str(vec![
+            ("A", "a b # c"),
+            ("B", "'single'"),
+            ("C", "\"double\""),
+            ("D", "it's $x and \\$y"),
+            ("E", "line\r\nnext\n"),
+            ("F", "  padded\t"),
+            ("G", "back\\slash \\n"),
+            ("H", ""),
+            ("I", "#x"),
+        ]);
+        let output = write_string(&items, &Options::new(Format::Dotenv)).unwrap();
+        let (entries, warnings) = dotenv::parse(output.as_bytes()).unwrap();
+        assert!(warnings.is_empty());
+        let parsed: Vec<_> = template::items(&entries)
+            .into_iter()
+            .map(|(k, v)| (k, interpolate::unescape(&v)))
+            .collect();
+        assert_eq!(parsed, items);
+    }
+}
@@ -0,0 +1,106 @@
+//! GitHub Actions `$GITHUB_ENV` and `$GITHUB_OUTPUT` files.
+//!
+//! Every entry is written in the `KEY<<DELIMITER` form, with a delimiter that
+//! does not occur in the value, so values can span multiple lines. Delimiters
+//! are derived from the entry, so the same items give the same file.
+//! Secret values are masked with `::add-mask::` commands, which `main` writes
+//! before the entries: to stdout, or to stderr when the entries go to stdout,
+//! so that `>> "$GITHUB_ENV"` does not put them into the file.
+
+use std::io::Write;
+
+use eyre::Result;
+use sha2::{Digest, Sha256};
+
+use super::{encode_str, Options};
+use crate::{EnvItem, Error};
+
+pub fn write(out: &mut dyn Write, items: &[EnvItem], options: &Options) -> Result<()> {
+    let (_, entries) = render(items, options)?;
+    out.write_all(entries.as_bytes())?;
+    Ok(())
+}
+
+/// The `::add-mask::` commands for the secret values.
+pub fn masks(items: &[EnvItem], options: &Options) -> Result<String> {
+    let (masks, _) = render(items, options)?;
+    Ok(masks)
+}
+
+/// The mask commands and the file entries.
+fn render(items: &[EnvItem], options: &Options) -> Result<(String, String)> {
+    let problems: Vec<String> = items
+        .iter()
+        .map(|(k, _)| k.to_string_lossy())
+        .filter(|k| k.is_empty() || k.contains(['\n', '\r', '=']) || k.contains("<<"))
+        .map(|k| format!("{:?}: not a valid name", k))
+        .collect();
+    if !problems.is_empty() {
+        return Err(Error::Unrepresentable { format: "GitHub Actions", problems }.into());
+    }
+
+    let mut masks = String::new();
+    let mut entries = String::new();
+    for (k, v) in items {
+        let key = encode_str(k, k, options.invalid_utf8)?;
+        let value = encode_str(v, k, options.invalid_utf8)?;
+        if options.secrets.is_secret(k) {
+            // Masks apply to single lines.
+            for line in value.lines().filter(|l| !l.trim().is_empty()) {
+                masks.push_str(&format!("::add-mask::{}\n", escape_data(line)));
+            }
+        }
+        let delimiter = (0u32..)
+            .map(|n| {
+                let digest = format!("{:x}", Sha256::digest(format!("{}\0{}\0{}", n, key, value).as_bytes()));
+                format!("ghadelimiter_{}", &digest[..16])
+            })
+            .find(|d| !value.contains(d.as_str()) && !key.contains(d.as_str()))
+            .expect("a delimiter that is not in the entry");
+        entries.push_str(&format!("{}<<{}\n{}\n{}\n", key, delimiter, value, delimiter));
+    }
+    Ok((masks, entries))
+}
+
+/// Escape the data of a workflow command.
+fn escape_data(s: &str) -> String {
+    s.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::Format;
+    use crate::secret::Secrets;
+    use crate::tests::to_os_str;
+
+    #[test]
+    fn test_render() {
+        let items = to_os_str(vec![("A", "1"), ("TOKEN", "abc\n50%\n")]);
+        let options = Options {
+            secrets: Secrets::new(&[String::from("TOKEN")], &[]),
+            ..Options::new(Format::GithubEnv)
+        };
+        let (masks, entries) = render(&items, &options).unwrap();
+        assert_eq!(masks, "::add-mask::abc\n::add-mask::50%25\n");
+
+        let lines: Vec<&str> = entries.lines().collect();
+        assert_eq!(lines.len(), 8);
+        let (key, delimiter) = lines[0].split_once("<<").unwrap();
+        assert_eq!((key, lines[1], lines[2]), ("A", "1", delimiter));
+        assert!(delimiter.starts_with("ghadelimiter_"));
+        let (key, delimiter) = lines[3].split_once("<<").unwrap();
+        assert_eq!((key, &lines[4..]), ("TOKEN", &["abc", "50%", "", delimiter][..]));
+
+        // The masks go to stdout through `preamble`, not into the file.
+        let written = crate::output::write_string(&items, &options).unwrap();
+        assert!(written.starts_with("A<<ghadelimiter_"));
+        assert!(!written.contains("::add-mask::"));
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        let items = to_os_str(vec![("A<<B", "1")]);
+        assert!(render(&items, &Options::new(Format::GithubEnv)).is_err());
+    }
+}
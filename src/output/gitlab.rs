@@ -0,0 +1,107 @@
+//! GitLab CI `artifacts:reports:dotenv` files.
+//!
+//! GitLab reads a restricted dotenv dialect: keys of letters, digits and `_`,
+//! single line values without surrounding whitespace or quotes, UTF-8 only,
+//! at most 5 KB and a limited number of variables. GitLab drops what it cannot
+//! read without failing the job, so every violation is reported up front.
+
+use std::io::Write;
+
+use eyre::Result;
+
+use super::{encode_str, Options};
+use crate::{EnvItem, Error};
+
+/// Default size limit of the report in bytes.
+const MAX_SIZE: usize = 5 * 1024;
+
+pub fn write(out: &mut dyn Write, items: &[EnvItem], options: &Options) -> Result<()> {
+    let mut problems = Vec::new();
+    let mut report = String::new();
+    for (k, v) in items {
+        let key = encode_str(k, k, options.invalid_utf8)?;
+        let value = encode_str(v, k, options.invalid_utf8)?;
+        match problem(&key, &value) {
+            Some(reason) => problems.push(format!("{}: {}", key, reason)),
+            None => report.push_str(&format!("{}={}\n", key, value)),
+        }
+    }
+    if items.len() > options.gitlab_max_variables {
+        problems.push(format!(
+            "{} variables, GitLab accepts at most {}",
+            items.len(),
+            options.gitlab_max_variables
+        ));
+    }
+    if report.len() > MAX_SIZE {
+        problems.push(format!("report is {} bytes, GitLab accepts at most {}", report.len(), MAX_SIZE));
+    }
+    if !problems.is_empty() {
+        return Err(Error::Unrepresentable { format: "GitLab dotenv", problems }.into());
+    }
+
+    out.write_all(report.as_bytes())?;
+    Ok(())
+}
+
+/// Why GitLab cannot read back the item, if it cannot.
+fn problem(key: &str, value: &str) -> Option<&'static str> {
+    let quoted = value.len() >= 2
+        && ((value.starts_with('"') && value.ends_with('"')) || (value.starts_with('\'') && value.ends_with('\'')));
+    let reason = if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        "key may only contain letters, digits and `_`"
+    } else if value.contains(['\n', '\r']) {
+        "multi-line values are not supported"
+    } else if value.trim() != value {
+        "leading or trailing whitespace would be removed"
+    } else if quoted {
+        "surrounding quotes would be removed"
+    } else {
+        return None;
+    };
+    Some(reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{write_string, Format};
+    use crate::tests::to_os_str;
+
+    #[test]
+    fn test_write() {
+        let items = to_os_str(vec![("A", "a=b c"), ("B_2", "")]);
+        let result = write_string(&items, &Options::new(Format::GitlabDotenv)).unwrap();
+        assert_eq!(result, "A=a=b c\nB_2=\n");
+    }
+
+    #[test]
+    fn test_problems() {
+        let items = to_os_str(vec![
+            ("A", "x\ny"),
+            ("B.C", "x"),
+            ("D", " x"),
+            ("E", "'x'"),
+            ("F", "ok"),
+        ]);
+        let mut options = Options::new(Format::GitlabDotenv);
+        options.gitlab_max_variables = 4;
+        let result = write_string(&items, &options).unwrap_err();
+        let Some(Error::Unrepresentable { problems, .. }) = result.downcast_ref::<Error>() else {
+            panic!("unexpected error {}", result);
+        };
+        assert_eq!(problems, &vec![
+            "A: multi-line values are not supported",
+            "B.C: key may only contain letters, digits and `_`",
+            "D: leading or trailing whitespace would be removed",
+            "E: surrounding quotes would be removed",
+            "5 variables, GitLab accepts at most 4",
+        ]);
+    }
+
+    #[test]
+    fn test_size() {
+        let items = to_os_str(vec![("A", &"x".repeat(MAX_SIZE))]);
+        assert!(write_string(&items, &Options::new(Format::GitlabDotenv)).is_err());
+    }
+}
@@ -0,0 +1,163 @@
+//! JSON output, as an object or as an array that keeps the order of the items.
+//!
+//! JSON strings cannot hold bytes that are not valid UTF-8, so such keys and
+//! values fail the output unless `--invalid-utf8 escape` is given. The same
+//! holds for the other text only formats.
+//!
+//! Keys must be unique in an object, as in TOML and YAML. Duplicates, which
+//! stripping prefixes can produce, fail the output.
+//!
+//! With `--annotate` the origin of each value is written as extra fields: in an
+//! object the value becomes `{"value": ..., "source": ...}`.
+
+use std::fmt::Write as _;
+use std::io::Write;
+
+use eyre::Result;
+
+use super::{check_unique, encode_str, origin, Options};
+use crate::provenance::Origin;
+use crate::EnvItem;
+
+pub fn write_object(out: &mut dyn Write, items: &[EnvItem], options: &Options) -> Result<()> {
+    check_unique(items, "JSON")?;
+    write_list(out, items, options, "{", "}", |k, v, fields| match fields {
+        "" => format!("{}: {}", string(k), string(v)),
+        _ => format!("{}: {{\"value\": {}{}}}", string(k), string(v), fields),
+    })
+}
+
+pub fn write_array(out: &mut dyn Write, items: &[EnvItem], options: &Options) -> Result<()> {
+    write_list(out, items, options, "[", "]", |k, v, fields| {
+        format!("{{\"key\": {}, \"value\": {}{}}}", string(k), string(v), fields)
+    })
+}
+
+fn write_list(
+    out: &mut dyn Write,
+    items: &[EnvItem],
+    options: &Options,
+    open: &str,
+    close: &str,
+    entry: impl Fn(&str, &str, &str) -> String,
+) -> Result<()> {
+    if items.is_empty() {
+        writeln!(out, "{}{}", open, close)?;
+        return Ok(());
+    }
+    writeln!(out, "{}", open)?;
+    for (i, (k, v)) in items.iter().enumerate() {
+        let key = encode_str(k, k, options.invalid_utf8)?;
+        let value = encode_str(v, k, options.invalid_utf8)?;
+        let fields = match origin(k, options) {
+            Some(origin) => origin_fields(origin, options)?,
+            None => String::new(),
+        };
+        let sep = if i + 1 < items.len() { "," } else { "" };
+        writeln!(out, "  {}{}", entry(&key, &value, &fields), sep)?;
+    }
+    writeln!(out, "{}", close)?;
+    Ok(())
+}
+
+/// The fields that describe `origin`, each preceded by a comma.
+fn origin_fields(origin: &Origin, options: &Options) -> Result<String> {
+    Ok(match origin {
+        Origin::Template { line } => format!(", \"source\": \"template\", \"line\": {}", line),
+        Origin::Env { name } => {
+            let name = encode_str(name, name, options.invalid_utf8)?;
+            format!(", \"source\": \"env\", \"name\": {}", string(&name))
+        }
+    })
+}
+
+/// A quoted JSON string.
+pub fn string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 || c == '\u{7f}' => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytes::to_os_string;
+    use crate::output::{write_string, Format, InvalidUtf8};
+    use crate::provenance;
+    use crate::tests::to_os_str;
+    use crate::Error;
+
+    #[test]
+    fn test_string() {
+        assert_eq!(string("a \"b\" \\ \n\t\u{1}é"), "\"a \\\"b\\\" \\\\ \\n\\t\\u0001é\"");
+    }
+
+    #[test]
+    fn test_write() {
+        let items = to_os_str(vec![("b", "1"), ("a", "x\"y")]);
+        let result = write_string(&items, &Options::new(Format::Json)).unwrap();
+        assert_eq!(result, "{\n  \"b\": \"1\",\n  \"a\": \"x\\\"y\"\n}\n");
+
+        let result = write_string(&items, &Options::new(Format::JsonArray)).unwrap();
+        let expect = "[\n  {\"key\": \"b\", \"value\": \"1\"},\n  {\"key\": \"a\", \"value\": \"x\\\"y\"}\n]\n";
+        assert_eq!(result, expect);
+
+        assert_eq!(write_string(&[], &Options::new(Format::Json)).unwrap(), "{}\n");
+    }
+
+    #[test]
+    fn test_write_duplicates() {
+        // As with `-p STAGING_` and both `STAGING_K` and `K` in the environment.
+        let items = to_os_str(vec![("K", "1"), ("A", "x"), ("K", "2")]);
+        let result = write_string(&items, &Options::new(Format::Json)).unwrap_err();
+        let Some(Error::Unrepresentable { problems, .. }) = result.downcast_ref::<Error>() else {
+            panic!("unexpected error {}", result);
+        };
+        assert_eq!(problems, &vec!["K: key occurs 2 times"]);
+
+        assert!(write_string(&items, &Options::new(Format::JsonArray)).is_ok());
+    }
+
+    #[test]
+    fn test_write_provenance() {
+        let items = vec![
+            ("A".into(), ("1".into(), Origin::Template { line: 3 })),
+            ("B".into(), ("2".into(), Origin::Env { name: "APP_B".into() })),
+        ];
+        let (items, provenance) = provenance::split(items);
+        let options = Options { provenance: Some(provenance), ..Options::new(Format::Json) };
+        let expect = concat!(
+            "{\n",
+            "  \"A\": {\"value\": \"1\", \"source\": \"template\", \"line\": 3},\n",
+            "  \"B\": {\"value\": \"2\", \"source\": \"env\", \"name\": \"APP_B\"}\n",
+            "}\n",
+        );
+        assert_eq!(write_string(&items, &options).unwrap(), expect);
+
+        let options = Options { format: Format::K8s, ..options };
+        assert!(write_string(&items, &options).is_err());
+    }
+
+    #[test]
+    fn test_write_non_utf8() {
+        let items = vec![("a".into(), to_os_string(b"\xFFx".to_vec()))];
+        assert!(write_string(&items, &Options::new(Format::Json)).is_err());
+
+        let options = Options { invalid_utf8: InvalidUtf8::Escape, ..Options::new(Format::Json) };
+        assert_eq!(write_string(&items, &options).unwrap(), "{\n  \"a\": \"\\\\xFFx\"\n}\n");
+    }
+}
@@ -0,0 +1,277 @@
+//! Kubernetes `ConfigMap` and `Secret` manifests, and the `env` list of a container.
+//!
+//! Secret values are base64 encoded in `data`, or written as they are in
+//! `stringData` with `--string-data`. ConfigMap values that are not valid
+//! UTF-8 go to `binaryData`, unless `--invalid-utf8` says otherwise.
+
+use std::ffi::OsString;
+use std::io::Write;
+
+use base64::prelude::{Engine, BASE64_STANDARD};
+use eyre::Result;
+
+use super::yaml::{quoted, scalar};
+use super::{check_unique, encode, encode_str, Options};
+use crate::{EnvItem, Error};
+
+/// Metadata and layout of the generated manifests.
+#[derive(Debug, Clone)]
+pub struct Manifest {
+    pub name: String,
+    pub namespace: Option<String>,
+    pub labels: Vec<(String, String)>,
+    /// Write Secret values to `stringData` instead of base64 encoded `data`.
+    pub string_data: bool,
+    /// In the container `env` list, refer to secret keys in the Secret named
+    /// `name` instead of writing their values.
+    pub secret_key_ref: bool,
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Manifest {
+            name: String::from("env"),
+            namespace: None,
+            labels: Vec::new(),
+            string_data: false,
+            secret_key_ref: false,
+        }
+    }
+}
+
+/// Parse a `key=value` label.
+pub fn parse_label(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((k, v)) if !k.is_empty() => Ok((k.to_string(), v.to_string())),
+        _ => Err(format!("expected `key=value`, got `{}`", s)),
+    }
+}
+
+/// A ConfigMap holding every item.
+pub fn write_config_map(out: &mut dyn Write, items: &[EnvItem], options: &Options) -> Result<()> {
+    check_keys(items)?;
+    config_map(out, items, options)
+}
+
+/// A Secret holding every item.
+pub fn write_secret(out: &mut dyn Write, items: &[EnvItem], options: &Options) -> Result<()> {
+    check_keys(items)?;
+    secret(out, items, options)
+}
+
+/// A ConfigMap with the plain items followed by a Secret with the secret ones.
+pub fn write_split(out: &mut dyn Write, items: &[EnvItem], options: &Options) -> Result<()> {
+    check_keys(items)?;
+    let (secrets, plain): (Vec<EnvItem>, Vec<EnvItem>) =
+        items.iter().cloned().partition(|(k, _)| options.secrets.is_secret(k));
+    config_map(out, &plain, options)?;
+    writeln!(out, "---")?;
+    secret(out, &secrets, options)
+}
+
+/// The `env` list of a container spec.
+pub fn write_env(out: &mut dyn Write, items: &[EnvItem], options: &Options) -> Result<()> {
+    let problems: Vec<String> = items
+        .iter()
+        .map(|(k, _)| k.to_string_lossy())
+        .filter(|k| k.is_empty() || !k.chars().all(|c| c.is_ascii_graphic() && c != '='))
+        .map(|k| format!("{}: not a valid environment variable name", k))
+        .collect();
+    if !problems.is_empty() {
+        return Err(Error::Unrepresentable { format: "Kubernetes", problems }.into());
+    }
+
+    if items.is_empty() {
+        writeln!(out, "[]")?;
+    }
+    for (k, v) in items {
+        let key = k.to_string_lossy();
+        writeln!(out, "- name: {}", quoted(&key))?;
+        if options.manifest.secret_key_ref && options.secrets.is_secret(k) {
+            writeln!(out, "  valueFrom:")?;
+            writeln!(out, "    secretKeyRef:")?;
+            writeln!(out, "      name: {}", scalar(&options.manifest.name))?;
+            writeln!(out, "      key: {}", quoted(&key))?;
+        } else {
+            writeln!(out, "  value: {}", quoted(&encode_str(v, k, options.invalid_utf8)?))?;
+        }
+    }
+    Ok(())
+}
+
+fn config_map(out: &mut dyn Write, items: &[EnvItem], options: &Options) -> Result<()> {
+    let mut data = Vec::new();
+    let mut binary_data = Vec::new();
+    for (k, v) in items {
+        let value = encode(v, k, options.invalid_utf8)?;
+        match std::str::from_utf8(&value) {
+            Ok(value) => data.push((k, scalar(value))),
+            Err(_) => binary_data.push((k, BASE64_STANDARD.encode(&value))),
+        }
+    }
+
+    header(out, "ConfigMap", &options.manifest)?;
+    mapping(out, "data", &data)?;
+    if !binary_data.is_empty() {
+        mapping(out, "binaryData", &binary_data)?;
+    }
+    Ok(())
+}
+
+fn secret(out: &mut dyn Write, items: &[EnvItem], options: &Options) -> Result<()> {
+    let mut data = Vec::new();
+    for (k, v) in items {
+        if options.manifest.string_data {
+            // stringData can only hold text.
+            let value = encode_str(v, k, options.invalid_utf8)?;
+            data.push((k, scalar(&value)));
+        } else {
+            data.push((k, BASE64_STANDARD.encode(encode(v, k, options.invalid_utf8)?)));
+        }
+    }
+
+    header(out, "Secret", &options.manifest)?;
+    writeln!(out, "type: Opaque")?;
+    let field = if options.manifest.string_data { "stringData" } else { "data" };
+    mapping(out, field, &data)
+}
+
+fn header(out: &mut dyn Write, kind: &str, manifest: &Manifest) -> Result<()> {
+    writeln!(out, "apiVersion: v1")?;
+    writeln!(out, "kind: {}", kind)?;
+    writeln!(out, "metadata:")?;
+    writeln!(out, "  name: {}", scalar(&manifest.name))?;
+    if let Some(namespace) = &manifest.namespace {
+        writeln!(out, "  namespace: {}", scalar(namespace))?;
+    }
+    if !manifest.labels.is_empty() {
+        writeln!(out, "  labels:")?;
+        for (k, v) in &manifest.labels {
+            writeln!(out, "    {}: {}", scalar(k), scalar(v))?;
+        }
+    }
+    Ok(())
+}
+
+/// A mapping of keys to already formatted YAML scalars.
+fn mapping(out: &mut dyn Write, field: &str, data: &[(&OsString, String)]) -> Result<()> {
+    if data.is_empty() {
+        writeln!(out, "{}: {{}}", field)?;
+        return Ok(());
+    }
+    writeln!(out, "{}:", field)?;
+    for (k, v) in data {
+        // Keys are checked by `check_keys`.
+        writeln!(out, "  {}: {}", scalar(&k.to_string_lossy()), v)?;
+    }
+    Ok(())
+}
+
+/// ConfigMap and Secret keys may only hold alphanumerics, `-`, `_` and `.`,
+/// and must be unique.
+fn check_keys(items: &[EnvItem]) -> Result<(), Error> {
+    check_unique(items, "Kubernetes")?;
+    let problems: Vec<String> = items
+        .iter()
+        .map(|(k, _)| k.to_string_lossy())
+        .filter(|k| k.is_empty() || !k.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+        .map(|k| format!("{}: not a valid ConfigMap or Secret key", k))
+        .collect();
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Unrepresentable { format: "Kubernetes", problems })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{write_string, Format};
+    use crate::secret::Secrets;
+    use crate::tests::to_os_str;
+
+    fn options(format: Format) -> Options {
+        Options {
+            manifest: Manifest {
+                name: String::from("web"),
+                namespace: Some(String::from("prod")),
+                labels: vec![(String::from("app"), String::from("web"))],
+                string_data: false,
+                secret_key_ref: true,
+            },
+            secrets: Secrets::new(&[String::from("SECRET_")], &[]),
+            ..Options::new(format)
+        }
+    }
+
+    #[test]
+    fn test_config_map() {
+        let items = to_os_str(vec![("A", "a=b"), ("ENABLED", "yes")]);
+        let result = write_string(&items, &options(Format::K8sConfigmap)).unwrap();
+        let expect = concat!(
+            "apiVersion: v1\n",
+            "kind: ConfigMap\n",
+            "metadata:\n",
+            "  name: web\n",
+            "  namespace: prod\n",
+            "  labels:\n",
+            "    app: web\n",
+            "data:\n",
+            "  A: \"a=b\"\n",
+            "  ENABLED: \"yes\"\n",
+        );
+        assert_eq!(result, expect);
+    }
+
+    #[test]
+    fn test_secret() {
+        let items = to_os_str(vec![("A", "a=b")]);
+        let result = write_string(&items, &Options::new(Format::K8sSecret)).unwrap();
+        let expect = "apiVersion: v1\nkind: Secret\nmetadata:\n  name: env\ntype: Opaque\ndata:\n  A: YT1i\n";
+        assert_eq!(result, expect);
+
+        let mut options = Options::new(Format::K8sSecret);
+        options.manifest.string_data = true;
+        let result = write_string(&items, &options).unwrap();
+        assert!(result.ends_with("stringData:\n  A: \"a=b\"\n"));
+    }
+
+    #[test]
+    fn test_split() {
+        let items = to_os_str(vec![("A", "1"), ("SECRET_B", "2")]);
+        let result = write_string(&items, &options(Format::K8s)).unwrap();
+        let (config_map, secret) = result.split_once("---\n").unwrap();
+        assert!(config_map.ends_with("data:\n  A: \"1\"\n"));
+        assert!(secret.ends_with("data:\n  SECRET_B: Mg==\n"));
+    }
+
+    #[test]
+    fn test_env() {
+        let items = to_os_str(vec![("PORT", "80"), ("SECRET_B", "2")]);
+        let result = write_string(&items, &Options::new(Format::K8sEnv)).unwrap();
+        let expect = "- name: \"PORT\"\n  value: \"80\"\n- name: \"SECRET_B\"\n  value: \"2\"\n";
+        assert_eq!(result, expect);
+
+        let result = write_string(&items, &options(Format::K8sEnv)).unwrap();
+        let expect = concat!(
+            "- name: \"PORT\"\n",
+            "  value: \"80\"\n",
+            "- name: \"SECRET_B\"\n",
+            "  valueFrom:\n",
+            "    secretKeyRef:\n",
+            "      name: web\n",
+            "      key: \"SECRET_B\"\n",
+        );
+        assert_eq!(result, expect);
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        let items = to_os_str(vec![("A B", "1")]);
+        assert!(write_string(&items, &Options::new(Format::K8s)).is_err());
+
+        let items = to_os_str(vec![("K", "1"), ("K", "2")]);
+        assert!(write_string(&items, &Options::new(Format::K8sConfigmap)).is_err());
+    }
+}
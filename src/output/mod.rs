@@ -0,0 +1,230 @@
+//! Output formats for the merged environment.
+
+mod docker;
+mod dotenv;
+mod github;
+mod gitlab;
+mod json;
+mod k8s;
+mod shell;
+mod systemd;
+mod toml;
+mod yaml;
+
+use std::borrow::Cow;
+use std::ffi::{OsStr, OsString};
+use std::io::Write;
+
+use clap::ArgEnum;
+use eyre::Result;
+
+use crate::provenance::{Origin, Provenance};
+use crate::redact::Redactor;
+use crate::secret::Secrets;
+use crate::{bytes, EnvItem, Error};
+use shell::Shell;
+
+pub use k8s::{parse_label, Manifest};
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// `KEY=value` lines
+    Dotenv,
+    /// A JSON object of keys to values
+    Json,
+    /// A JSON array of `{"key": ..., "value": ...}` objects, in output order
+    JsonArray,
+    /// A YAML mapping of keys to string values
+    Yaml,
+    /// A TOML table of string values
+    Toml,
+    /// `export` statements for bash, zsh and other POSIX shells
+    Sh,
+    /// `set -gx` statements for fish
+    Fish,
+    /// `$env:` assignments for PowerShell
+    Pwsh,
+    /// `$env.` assignments for nushell
+    Nu,
+    /// A file for `docker run --env-file`
+    Docker,
+    /// A Kubernetes ConfigMap with every key
+    K8sConfigmap,
+    /// A Kubernetes Secret with every key
+    K8sSecret,
+    /// A Kubernetes ConfigMap with the plain keys and a Secret with the secret ones
+    K8s,
+    /// The `env` list of a Kubernetes container spec
+    K8sEnv,
+    /// Entries for `$GITHUB_ENV`, appended to the output file instead of replacing it
+    GithubEnv,
+    /// Entries for `$GITHUB_OUTPUT`, appended to the output file instead of replacing it
+    GithubOutput,
+    /// A GitLab CI `artifacts:reports:dotenv` file
+    GitlabDotenv,
+    /// A file for systemd's `EnvironmentFile=`
+    Systemd,
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InvalidUtf8 {
+    /// Write the bytes unchanged, or fail for formats that cannot hold them
+    Keep,
+    /// Fail with an error
+    Error,
+    /// Write invalid bytes as `\xNN`
+    Escape,
+}
+
+pub struct Options {
+    pub format: Format,
+    pub invalid_utf8: InvalidUtf8,
+    pub secrets: Secrets,
+    pub manifest: Manifest,
+    pub gitlab_max_variables: usize,
+    /// Mask sensitive values, in every format.
+    pub redact: Option<Redactor>,
+    /// Where the values came from, to be written with `--annotate`.
+    pub provenance: Option<Provenance>,
+}
+
+impl Format {
+    /// Whether the format can say where each value came from.
+    fn has_annotations(self) -> bool {
+        !matches!(
+            self,
+            Format::K8sConfigmap
+                | Format::K8sSecret
+                | Format::K8s
+                | Format::K8sEnv
+                | Format::GithubEnv
+                | Format::GithubOutput
+                | Format::GitlabDotenv
+        )
+    }
+
+    /// Whether the output file is appended to, for files that others read as
+    /// they grow, like `$GITHUB_ENV`.
+    pub fn appends(self) -> bool {
+        matches!(self, Format::GithubEnv | Format::GithubOutput)
+    }
+}
+
+/// Write `items` to `out` in the format selected by `options`.
+pub fn write(out: &mut dyn Write, items: &[EnvItem], options: &Options) -> Result<()> {
+    if options.provenance.is_some() && !options.format.has_annotations() {
+        let format = options.format.to_possible_value().map(|v| v.get_name()).unwrap_or_default();
+        return Err(Error::AnnotateUnsupported { format: format.to_string() }.into());
+    }
+    let redacted;
+    let items = match &options.redact {
+        Some(redactor) => {
+            redacted = redactor.apply(items, &options.secrets);
+            &redacted[..]
+        }
+        None => items,
+    };
+    match options.format {
+        Format::Dotenv => dotenv::write(out, items, options),
+        Format::Json => json::write_object(out, items, options),
+        Format::JsonArray => json::write_array(out, items, options),
+        Format::Yaml => yaml::write(out, items, options),
+        Format::Toml => toml::write(out, items, options),
+        Format::Sh => shell::write(out, items, options, Shell::Sh),
+        Format::Fish => shell::write(out, items, options, Shell::Fish),
+        Format::Pwsh => shell::write(out, items, options, Shell::Pwsh),
+        Format::Nu => shell::write(out, items, options, Shell::Nu),
+        Format::Docker => docker::write(out, items, options),
+        Format::K8sConfigmap => k8s::write_config_map(out, items, options),
+        Format::K8sSecret => k8s::write_secret(out, items, options),
+        Format::K8s => k8s::write_split(out, items, options),
+        Format::K8sEnv => k8s::write_env(out, items, options),
+        Format::GithubEnv | Format::GithubOutput => github::write(out, items, options),
+        Format::GitlabDotenv => gitlab::write(out, items, options),
+        Format::Systemd => systemd::write(out, items, options),
+    }
+}
+
+/// What to write ahead of the output, to stdout or, when the output goes to
+/// stdout, to stderr. These are the `::add-mask::` commands of the GitHub formats.
+pub fn preamble(items: &[EnvItem], options: &Options) -> Result<String> {
+    match options.format {
+        Format::GithubEnv | Format::GithubOutput => github::masks(items, options),
+        _ => Ok(String::new()),
+    }
+}
+
+/// The origin of `key`, with `--annotate`.
+fn origin<'a>(key: &OsString, options: &'a Options) -> Option<&'a Origin> {
+    options.provenance.as_ref()?.get(key)
+}
+
+/// The `# ...` line to write above `key`, with `--annotate`.
+fn comment(key: &OsString, options: &Options) -> String {
+    match origin(key, options) {
+        Some(origin) => format!("# {}\n", origin.describe()),
+        None => String::new(),
+    }
+}
+
+/// Fail when a key occurs more than once, for formats that write a mapping,
+/// where readers disagree on which of the values counts.
+fn check_unique(items: &[EnvItem], format: &'static str) -> Result<(), Error> {
+    let mut problems = Vec::new();
+    for (i, (k, _)) in items.iter().enumerate() {
+        let count = items.iter().filter(|(other, _)| other == k).count();
+        if count > 1 && !items[..i].iter().any(|(other, _)| other == k) {
+            problems.push(format!("{}: key occurs {} times", k.to_string_lossy(), count));
+        }
+    }
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Unrepresentable { format, problems })
+    }
+}
+
+/// Bytes of `s` as they should be written, according to `invalid`.
+/// `key` names the item in errors.
+fn encode<'a>(s: &'a OsStr, key: &OsStr, invalid: InvalidUtf8) -> Result<Cow<'a, [u8]>, Error> {
+    let b = bytes::as_bytes(s);
+    if std::str::from_utf8(b).is_ok() {
+        return Ok(Cow::Borrowed(b));
+    }
+    match invalid {
+        InvalidUtf8::Keep => Ok(Cow::Borrowed(b)),
+        InvalidUtf8::Error => Err(Error::InvalidUtf8 { key: key.to_string_lossy().into_owned() }),
+        InvalidUtf8::Escape => Ok(Cow::Owned(bytes::escape_invalid(b).into_bytes())),
+    }
+}
+
+/// Like `encode`, for formats that can only hold text. `Keep` is treated like `Error`.
+fn encode_str<'a>(s: &'a OsStr, key: &OsStr, invalid: InvalidUtf8) -> Result<Cow<'a, str>, Error> {
+    let invalid = if invalid == InvalidUtf8::Keep { InvalidUtf8::Error } else { invalid };
+    Ok(match encode(s, key, invalid)? {
+        Cow::Borrowed(b) => Cow::Borrowed(std::str::from_utf8(b).expect("checked by encode")),
+        Cow::Owned(b) => Cow::Owned(String::from_utf8(b).expect("escaped by encode")),
+    })
+}
+
+#[cfg(test)]
+pub(crate) fn write_string(items: &[EnvItem], options: &Options) -> Result<String> {
+    let mut out = Vec::new();
+    write(&mut out, items, options)?;
+    Ok(String::from_utf8(out)?)
+}
+
+#[cfg(test)]
+impl Options {
+    pub(crate) fn new(format: Format) -> Self {
+        Options {
+            format,
+            invalid_utf8: InvalidUtf8::Keep,
+            secrets: Secrets::default(),
+            manifest: Manifest::default(),
+            gitlab_max_variables: 50,
+            redact: None,
+            provenance: None,
+        }
+    }
+}
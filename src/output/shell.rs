@@ -0,0 +1,195 @@
+//! Shell scripts that set the environment when evaluated, for example with
+//! `eval "$(dump-env -f sh)"`.
+//!
+//! Values are single quoted where the shell allows it, so nothing in them is
+//! expanded. Keys must be valid variable names for the shell.
+
+use std::io::Write;
+
+use eyre::Result;
+
+use super::{comment, encode, encode_str, Options};
+use crate::{EnvItem, Error};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shell {
+    /// bash, zsh and other POSIX shells
+    Sh,
+    Fish,
+    Pwsh,
+    Nu,
+}
+
+impl Shell {
+    fn name(self) -> &'static str {
+        match self {
+            Shell::Sh => "sh",
+            Shell::Fish => "fish",
+            Shell::Pwsh => "pwsh",
+            Shell::Nu => "nu",
+        }
+    }
+
+    fn is_valid_key(self, key: &[u8]) -> bool {
+        let name_byte = |b: &u8| b.is_ascii_alphanumeric() || *b == b'_';
+        match self {
+            // fish allows names that start with a digit.
+            Shell::Fish => !key.is_empty() && key.iter().all(name_byte),
+            Shell::Sh | Shell::Pwsh | Shell::Nu => {
+                key.first().is_some_and(|b| !b.is_ascii_digit()) && key.iter().all(name_byte)
+            }
+        }
+    }
+}
+
+pub fn write(out: &mut dyn Write, items: &[EnvItem], options: &Options, shell: Shell) -> Result<()> {
+    for (k, v) in items {
+        let key = encode(k, k, options.invalid_utf8)?;
+        if !shell.is_valid_key(&key) {
+            return Err(Error::InvalidKey {
+                key: k.to_string_lossy().into_owned(),
+                reason: format!("not a valid {} variable name", shell.name()),
+            }
+            .into());
+        }
+        // Checked above to be ASCII.
+        let key = String::from_utf8_lossy(&key);
+        out.write_all(comment(k, options).as_bytes())?;
+
+        match shell {
+            Shell::Sh => {
+                let value = encode(v, k, options.invalid_utf8)?;
+                out.write_all(format!("export {}=", key).as_bytes())?;
+                out.write_all(&sh_quote(&value))?;
+            }
+            Shell::Fish => {
+                let value = encode(v, k, options.invalid_utf8)?;
+                out.write_all(format!("set -gx {} ", key).as_bytes())?;
+                out.write_all(&fish_quote(&value))?;
+            }
+            Shell::Pwsh => {
+                let value = encode_str(v, k, options.invalid_utf8)?;
+                write!(out, "$env:{} = {}", key, pwsh_quote(&value))?;
+            }
+            Shell::Nu => {
+                let value = encode_str(v, k, options.invalid_utf8)?;
+                write!(out, "$env.{} = {}", key, nu_quote(&value))?;
+            }
+        }
+        out.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// POSIX single quotes. A `'` ends the quotes, is escaped and reopens them.
+pub fn sh_quote(value: &[u8]) -> Vec<u8> {
+    let mut quoted = vec![b'\''];
+    for b in value {
+        match b {
+            b'\'' => quoted.extend_from_slice(b"'\\''"),
+            b => quoted.push(*b),
+        }
+    }
+    quoted.push(b'\'');
+    quoted
+}
+
+/// fish single quotes, where only `\'` and `\\` are escapes.
+fn fish_quote(value: &[u8]) -> Vec<u8> {
+    let mut quoted = vec![b'\''];
+    for b in value {
+        if matches!(b, b'\'' | b'\\') {
+            quoted.push(b'\\');
+        }
+        quoted.push(*b);
+    }
+    quoted.push(b'\'');
+    quoted
+}
+
+/// PowerShell single quotes. Quotes are escaped by doubling them, and
+/// PowerShell also accepts the typographic single quotes as quotes.
+fn pwsh_quote(value: &str) -> String {
+    let mut quoted = String::from("'");
+    for c in value.chars() {
+        if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}') {
+            quoted.push(c);
+        }
+        quoted.push(c);
+    }
+    quoted.push('\'');
+    quoted
+}
+
+/// nushell double quotes. Single quoted strings in nushell cannot hold a `'`.
+fn nu_quote(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{write_string, Format};
+    use crate::tests::to_os_str;
+    #[cfg(unix)]
+    use std::process::Command;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(sh_quote(b"it's $HOME\n"), b"'it'\\''s $HOME\n'");
+        assert_eq!(fish_quote(b"it's \\n"), b"'it\\'s \\\\n'");
+        assert_eq!(pwsh_quote("it's \u{2019}$x"), "'it''s \u{2019}\u{2019}$x'");
+        assert_eq!(nu_quote("a \"b\" \\ $x (y)\n\u{1b}"), "\"a \\\"b\\\" \\\\ $x (y)\\n\\u{1b}\"");
+    }
+
+    #[test]
+    fn test_write() {
+        let items = to_os_str(vec![("A", "it's"), ("B_2", "")]);
+        let cases = [
+            (Format::Sh, "export A='it'\\''s'\nexport B_2=''\n"),
+            (Format::Fish, "set -gx A 'it\\'s'\nset -gx B_2 ''\n"),
+            (Format::Pwsh, "$env:A = 'it''s'\n$env:B_2 = ''\n"),
+            (Format::Nu, "$env.A = \"it's\"\n$env.B_2 = \"\"\n"),
+        ];
+        for (format, expect) in cases {
+            assert_eq!(write_string(&items, &Options::new(format)).unwrap(), expect);
+        }
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        for key in ["1A", "a.b", "a b", ""] {
+            let items = to_os_str(vec![(key, "x")]);
+            assert!(write_string(&items, &Options::new(Format::Sh)).is_err(), "{}", key);
+        }
+        let items = to_os_str(vec![("1A", "x")]);
+        assert!(write_string(&items, &Options::new(Format::Fish)).is_ok());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_sh_round_trip() {
+        let value = "a 'b' \"c\" \\ $HOME `id` $(id) !x\n\ttail\u{e9}";
+        let items = to_os_str(vec![("DUMP_ENV_TEST", value)]);
+        let script = write_string(&items, &Options::new(Format::Sh)).unwrap();
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(format!("{}printf '%s' \"$DUMP_ENV_TEST\"", script))
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), value);
+    }
+}
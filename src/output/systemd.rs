@@ -0,0 +1,73 @@
+//! Files for systemd's `EnvironmentFile=`.
+//!
+//! Values are double quoted with `"`, `\`, `` ` `` and `$` escaped, which
+//! systemd reads back exactly, newlines included. systemd only accepts UTF-8
+//! and keys that are valid shell variable names.
+
+use std::io::Write;
+
+use eyre::Result;
+
+use super::{comment, encode_str, Options};
+use crate::{EnvItem, Error};
+
+pub fn write(out: &mut dyn Write, items: &[EnvItem], options: &Options) -> Result<()> {
+    let mut problems = Vec::new();
+    let mut lines = String::new();
+    for (k, v) in items {
+        let key = encode_str(k, k, options.invalid_utf8)?;
+        let value = encode_str(v, k, options.invalid_utf8)?;
+        let valid = key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if valid {
+            lines.push_str(&comment(k, options));
+            lines.push_str(&format!("{}={}\n", key, quote(&value)));
+        } else {
+            problems.push(format!("{}: not a valid variable name", key));
+        }
+    }
+    if !problems.is_empty() {
+        return Err(Error::Unrepresentable { format: "systemd", problems }.into());
+    }
+
+    out.write_all(lines.as_bytes())?;
+    Ok(())
+}
+
+fn quote(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | '`' | '$') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{write_string, Format};
+    use crate::tests::to_os_str;
+    use crate::{systemd, template};
+
+    #[test]
+    fn test_write() {
+        let items = to_os_str(vec![("A", "it's \"x\" $y `z` \\"), ("B", "multi\nline")]);
+        let result = write_string(&items, &Options::new(Format::Systemd)).unwrap();
+        assert_eq!(result, "A=\"it's \\\"x\\\" \\$y \\`z\\` \\\\\"\nB=\"multi\nline\"\n");
+
+        // Reading it back gives the same values, with `$` escaped for interpolation.
+        let (entries, _) = systemd::parse(result.as_bytes()).unwrap();
+        let expect = to_os_str(vec![("A", "it's \"x\" $$y `z` \\"), ("B", "multi\nline")]);
+        assert_eq!(template::items(&entries), expect);
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        let items = to_os_str(vec![("1A", "x")]);
+        assert!(write_string(&items, &Options::new(Format::Systemd)).is_err());
+    }
+}
@@ -0,0 +1,47 @@
+//! TOML output, a table of string values. Duplicate keys fail the output.
+
+use std::io::Write;
+
+use eyre::Result;
+
+use super::{check_unique, comment, encode_str, json, Options};
+use crate::EnvItem;
+
+pub fn write(out: &mut dyn Write, items: &[EnvItem], options: &Options) -> Result<()> {
+    check_unique(items, "TOML")?;
+    for (k, v) in items {
+        let key = encode_str(k, k, options.invalid_utf8)?;
+        let value = encode_str(v, k, options.invalid_utf8)?;
+        // JSON string escapes are a subset of the TOML basic string escapes.
+        write!(out, "{}", comment(k, options))?;
+        writeln!(out, "{} = {}", key_name(&key), json::string(&value))?;
+    }
+    Ok(())
+}
+
+/// A bare key when possible, a quoted key otherwise.
+fn key_name(key: &str) -> String {
+    let bare = !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        key.to_string()
+    } else {
+        json::string(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{write_string, Format};
+    use crate::tests::to_os_str;
+
+    #[test]
+    fn test_write() {
+        let items = to_os_str(vec![("PORT", "0123"), ("a.b", "x\"y\n"), ("ON", "true")]);
+        let result = write_string(&items, &Options::new(Format::Toml)).unwrap();
+        assert_eq!(result, "PORT = \"0123\"\n\"a.b\" = \"x\\\"y\\n\"\nON = \"true\"\n");
+
+        let items = to_os_str(vec![("K", "1"), ("K", "2")]);
+        assert!(write_string(&items, &Options::new(Format::Toml)).is_err());
+    }
+}
@@ -0,0 +1,95 @@
+//! YAML output, a mapping of keys to string values.
+//!
+//! Scalars are written plain only when no YAML 1.1 or 1.2 parser can read them
+//! as anything but a string. Everything else is double quoted, so values like
+//! `yes`, `0123` or `null` stay strings. Duplicate keys fail the output.
+
+use std::io::Write;
+
+use eyre::Result;
+
+use super::{check_unique, comment, encode_str, json, Options};
+use crate::EnvItem;
+
+pub fn write(out: &mut dyn Write, items: &[EnvItem], options: &Options) -> Result<()> {
+    check_unique(items, "YAML")?;
+    if items.is_empty() {
+        writeln!(out, "{{}}")?;
+    }
+    for (k, v) in items {
+        let key = encode_str(k, k, options.invalid_utf8)?;
+        let value = encode_str(v, k, options.invalid_utf8)?;
+        write!(out, "{}", comment(k, options))?;
+        writeln!(out, "{}: {}", scalar(&key), scalar(&value))?;
+    }
+    Ok(())
+}
+
+/// A YAML scalar that always reads back as the string `s`.
+pub fn scalar(s: &str) -> String {
+    if is_plain_safe(s) {
+        s.to_string()
+    } else {
+        quoted(s)
+    }
+}
+
+/// A double quoted YAML scalar.
+pub fn quoted(s: &str) -> String {
+    // A JSON string is a valid double quoted YAML scalar, except for the
+    // characters YAML 1.1 treats as line breaks.
+    json::string(s)
+        .replace('\u{85}', "\\N")
+        .replace('\u{2028}', "\\L")
+        .replace('\u{2029}', "\\P")
+}
+
+fn is_plain_safe(s: &str) -> bool {
+    // Booleans and null in YAML 1.1 and 1.2.
+    const RESERVED: [&str; 10] = ["y", "n", "yes", "no", "on", "off", "true", "false", "null", "~"];
+
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '/'))
+        && !RESERVED.iter().any(|r| r.eq_ignore_ascii_case(s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{write_string, Format};
+    use crate::tests::to_os_str;
+
+    #[test]
+    fn test_scalar() {
+        for plain in ["abc", "DB_HOST", "localhost", "a.b/c-d", "_x1"] {
+            assert_eq!(scalar(plain), plain);
+        }
+        let quoted = [
+            ("yes", "\"yes\""),
+            ("No", "\"No\""),
+            ("null", "\"null\""),
+            ("~", "\"~\""),
+            ("0123", "\"0123\""),
+            ("1e3", "\"1e3\""),
+            ("", "\"\""),
+            ("a b", "\"a b\""),
+            ("a: b", "\"a: b\""),
+            ("- x", "\"- x\""),
+            ("line\nnext", "\"line\\nnext\""),
+            ("x\u{2028}y", "\"x\\Ly\""),
+        ];
+        for (s, expect) in quoted {
+            assert_eq!(scalar(s), expect);
+        }
+    }
+
+    #[test]
+    fn test_write() {
+        let items = to_os_str(vec![("DEBUG", "yes"), ("PORT", "0123"), ("NAME", "app")]);
+        let result = write_string(&items, &Options::new(Format::Yaml)).unwrap();
+        assert_eq!(result, "DEBUG: \"yes\"\nPORT: \"0123\"\nNAME: app\n");
+
+        let items = to_os_str(vec![("K", "1"), ("K", "2")]);
+        assert!(write_string(&items, &Options::new(Format::Yaml)).is_err());
+    }
+}
@@ -0,0 +1,80 @@
+//! Where each value of the merged environment came from.
+//!
+//! The joins carry an `Origin` next to every value, so that `--annotate` can
+//! tell a template default from a variable of the environment, and name the
+//! variable as it was before `--prefixes` stripped it.
+
+use std::ffi::OsString;
+
+use crate::template::Entry;
+use crate::EnvItems;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    /// The default value of the template entry at this line.
+    Template { line: usize },
+    /// The environment variable with this name.
+    Env { name: OsString },
+}
+
+impl Origin {
+    /// Text for a comment next to the entry.
+    pub fn describe(&self) -> String {
+        match self {
+            Origin::Template { line } => format!("template default (line {})", line),
+            Origin::Env { name } => format!("from env {}", name.to_string_lossy()),
+        }
+    }
+}
+
+/// Items whose values carry their origin.
+pub type Sourced = Vec<(OsString, (OsString, Origin))>;
+
+/// The template entries with their lines as origin.
+pub fn from_template(entries: &[Entry]) -> Sourced {
+    entries
+        .iter()
+        .map(|e| (e.key.clone(), (e.value.clone(), Origin::Template { line: e.line })))
+        .collect()
+}
+
+/// Separate the origins from the values.
+pub fn split(items: Sourced) -> (EnvItems, Provenance) {
+    let mut origins = Vec::with_capacity(items.len());
+    let items = items
+        .into_iter()
+        .map(|(k, (v, origin))| {
+            origins.push((k.clone(), origin));
+            (k, v)
+        })
+        .collect();
+    (items, Provenance(origins))
+}
+
+/// The origin of each key.
+#[derive(Debug, Default)]
+pub struct Provenance(Vec<(OsString, Origin)>);
+
+impl Provenance {
+    pub fn get(&self, key: &OsString) -> Option<&Origin> {
+        self.0.iter().find(|(k, _)| k == key).map(|(_, origin)| origin)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split() {
+        let items: Sourced = vec![
+            ("A".into(), ("1".into(), Origin::Template { line: 3 })),
+            ("B".into(), ("2".into(), Origin::Env { name: "APP_B".into() })),
+        ];
+        let (items, provenance) = split(items);
+        assert_eq!(items, crate::tests::to_os_str(vec![("A", "1"), ("B", "2")]));
+        assert_eq!(provenance.get(&"A".into()).unwrap().describe(), "template default (line 3)");
+        assert_eq!(provenance.get(&"B".into()).unwrap().describe(), "from env APP_B");
+        assert_eq!(provenance.get(&"C".into()), None);
+    }
+}
@@ -0,0 +1,103 @@
+//! Masking of sensitive values, for dumps that end up in logs.
+//!
+//! A value is sensitive when its key is secret, see the `secret` module, or
+//! when the key looks like a credential by name, like `*_TOKEN`.
+
+use std::ffi::{OsStr, OsString};
+
+use clap::ArgEnum;
+use sha2::{Digest, Sha256};
+
+use crate::filter::Pattern;
+use crate::secret::Secrets;
+use crate::{bytes, EnvItem, EnvItems};
+
+/// Keys matching these are sensitive, whether or not they are marked secret.
+const PATTERNS: &[&str] = &[
+    "*_TOKEN",
+    "*_PASSWORD",
+    "*_PASSWD",
+    "*_SECRET",
+    "*_KEY",
+    "*_CREDENTIALS",
+    "*_API_KEY",
+    "PASSWORD",
+    "SECRET",
+    "TOKEN",
+];
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Redact {
+    /// Replace values with `***`
+    Stars,
+    /// Replace values with a short prefix of their SHA-256, to tell whether they changed
+    Hash,
+}
+
+impl Redact {
+    fn mask(self, value: &OsStr) -> String {
+        match self {
+            Redact::Stars => String::from("***"),
+            Redact::Hash => {
+                let digest = format!("{:x}", Sha256::digest(bytes::as_bytes(value)));
+                format!("sha256:{}", &digest[..12])
+            }
+        }
+    }
+}
+
+/// Tells sensitive keys apart, with the built-in patterns compiled once.
+pub struct Sensitive(Vec<Pattern>);
+
+impl Sensitive {
+    pub fn builtin() -> Self {
+        Sensitive(PATTERNS.iter().map(|p| Pattern::parse(p).expect("valid built-in pattern")).collect())
+    }
+
+    pub fn is_match(&self, key: &OsStr, secrets: &Secrets) -> bool {
+        secrets.is_secret(key) || self.0.iter().any(|p| p.is_match(bytes::as_bytes(key)))
+    }
+}
+
+/// Masks the values of sensitive keys.
+pub struct Redactor {
+    redact: Redact,
+    sensitive: Sensitive,
+}
+
+impl Redactor {
+    pub fn new(redact: Redact) -> Self {
+        Redactor { redact, sensitive: Sensitive::builtin() }
+    }
+
+    /// `value`, masked when `key` is sensitive. Empty values stay empty.
+    pub fn value(&self, key: &OsStr, value: &OsStr, secrets: &Secrets) -> OsString {
+        if value.is_empty() || !self.sensitive.is_match(key, secrets) {
+            return value.to_owned();
+        }
+        self.redact.mask(value).into()
+    }
+
+    /// `items` with the values of sensitive keys masked.
+    pub fn apply(&self, items: &[EnvItem], secrets: &Secrets) -> EnvItems {
+        items.iter().map(|(k, v)| (k.clone(), self.value(k, v, secrets))).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::to_os_str;
+
+    #[test]
+    fn test_apply() {
+        let items = to_os_str(vec![("GITHUB_TOKEN", "ghp_x"), ("DB_PASSWORD", ""), ("S_HOST", "db"), ("HOST", "db")]);
+        let secrets = Secrets::new(&[String::from("S_")], &[]);
+        let expect = to_os_str(vec![("GITHUB_TOKEN", "***"), ("DB_PASSWORD", ""), ("S_HOST", "***"), ("HOST", "db")]);
+        assert_eq!(Redactor::new(Redact::Stars).apply(&items, &secrets), expect);
+
+        let items = to_os_str(vec![("TOKEN", "abc")]);
+        let expect = to_os_str(vec![("TOKEN", "sha256:ba7816bf8f01")]);
+        assert_eq!(Redactor::new(Redact::Hash).apply(&items, &secrets), expect);
+    }
+}
@@ -0,0 +1,165 @@
+//! Value validation against the schema annotations of a template.
+//!
+//! * `# @type T` where `T` is one of `string`, `int`, `float`, `bool`, `port` or `url`.
+//! * `# @enum a|b|c` allows only the listed values.
+//! * `# @pattern REGEX` requires the value to match the regular expression.
+//!
+//! Empty values are not validated, use `@required` to reject them. Messages
+//! show values the way the caller renders them, so that sensitive values can
+//! be left out.
+
+use std::ffi::OsStr;
+
+use regex::Regex;
+
+use crate::template::{Annotation, Entry};
+use crate::{EnvItem, Error};
+
+/// Check every resolved value in `items` against the annotations of its
+/// template entry. All violations are reported at once. `shown` renders a
+/// value of a key for the messages.
+pub fn validate(entries: &[Entry], items: &[EnvItem], shown: impl Fn(&OsStr, &OsStr) -> String) -> Result<(), Error> {
+    let mut violations = Vec::new();
+    for entry in entries {
+        let Some((_, value)) = items.iter().find(|(k, _)| k == &entry.key) else {
+            continue;
+        };
+        if value.is_empty() {
+            continue;
+        }
+        for annotation in &entry.annotations {
+            let result = match value.to_str() {
+                Some(v) => check(annotation, v).map_err(|e| format!("{} {}", shown(&entry.key, value), e)),
+                None => Err(String::from("value is not valid UTF-8")),
+            };
+            if let Err(message) = result {
+                violations.push(format!("{} (line {}): {}", entry.key.to_string_lossy(), annotation.line, message));
+            }
+        }
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Validation { violations })
+    }
+}
+
+/// Check a single value against an annotation. The message says what was
+/// expected, to follow the value.
+/// Annotations that are not part of the schema always pass.
+fn check(annotation: &Annotation, value: &str) -> Result<(), String> {
+    let arg = annotation.argument.as_str();
+    let valid = match annotation.name.as_str() {
+        "type" => match arg {
+            "string" => true,
+            "int" => value.parse::<i64>().is_ok(),
+            "float" => value.parse::<f64>().is_ok(),
+            "bool" => is_bool(value),
+            "port" => value.parse::<u16>().is_ok_and(|p| p > 0),
+            "url" => is_url(value),
+            _ => return Err(format!("unknown type `{}`", arg)),
+        },
+        "enum" => arg.split('|').any(|x| x.trim() == value),
+        "pattern" => {
+            let re = Regex::new(arg).map_err(|e| format!("invalid pattern `{}`: {}", arg, e))?;
+            re.is_match(value)
+        }
+        _ => true,
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(match annotation.name.as_str() {
+            "type" => format!("is not a valid {}", arg),
+            "enum" => format!("is not one of {}", arg),
+            _ => format!("does not match `{}`", arg),
+        })
+    }
+}
+
+fn is_bool(value: &str) -> bool {
+    let bools = ["true", "false", "1", "0", "yes", "no", "on", "off"];
+    bools.iter().any(|b| b.eq_ignore_ascii_case(value))
+}
+
+/// A `scheme://rest` URL without whitespace.
+fn is_url(value: &str) -> bool {
+    let Some((scheme, rest)) = value.split_once("://") else {
+        return false;
+    };
+    let scheme_ok = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    scheme_ok && !rest.is_empty() && !value.contains(char::is_whitespace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::to_os_str;
+
+    fn shown(_: &OsStr, v: &OsStr) -> String {
+        format!("`{}`", v.to_string_lossy())
+    }
+
+    fn annotation(name: &str, argument: &str) -> Annotation {
+        Annotation { name: name.into(), argument: argument.into(), line: 1 }
+    }
+
+    #[test]
+    fn test_check() {
+        assert!(check(&annotation("type", "int"), "-12").is_ok());
+        assert!(check(&annotation("type", "int"), "80a").is_err());
+        assert!(check(&annotation("type", "float"), "1.5").is_ok());
+        assert!(check(&annotation("type", "bool"), "Yes").is_ok());
+        assert!(check(&annotation("type", "bool"), "y").is_err());
+        assert!(check(&annotation("type", "port"), "8080").is_ok());
+        assert!(check(&annotation("type", "port"), "0").is_err());
+        assert!(check(&annotation("type", "port"), "65536").is_err());
+        assert!(check(&annotation("type", "url"), "postgres://u:p@db/app").is_ok());
+        assert!(check(&annotation("type", "url"), "db:5432").is_err());
+        assert!(check(&annotation("type", "url"), "http://a b").is_err());
+        assert!(check(&annotation("type", "uuid"), "x").is_err());
+        assert!(check(&annotation("enum", "debug|info|warn"), "info").is_ok());
+        assert!(check(&annotation("enum", "debug|info|warn"), "trace").is_err());
+        assert!(check(&annotation("pattern", "^[a-z]+$"), "abc").is_ok());
+        assert!(check(&annotation("pattern", "^[a-z]+$"), "abc1").is_err());
+        assert!(check(&annotation("pattern", "("), "abc").is_err());
+        assert!(check(&annotation("required", ""), "abc").is_ok());
+    }
+
+    #[test]
+    fn test_validate() {
+        let entries = vec![
+            Entry {
+                key: "PORT".into(),
+                value: "80".into(),
+                line: 3,
+                annotations: vec![Annotation { name: "type".into(), argument: "int".into(), line: 2 }],
+            },
+            Entry {
+                key: "LEVEL".into(),
+                value: "".into(),
+                line: 5,
+                annotations: vec![Annotation { name: "enum".into(), argument: "debug|info".into(), line: 4 }],
+            },
+        ];
+
+        let items = to_os_str(vec![("PORT", "80a"), ("LEVEL", "trace")]);
+        let result = validate(&entries, &items, shown);
+        assert!(matches!(result, Err(Error::Validation { violations }) if violations == vec![
+            "PORT (line 2): `80a` is not a valid int",
+            "LEVEL (line 4): `trace` is not one of debug|info",
+        ]));
+
+        let items = to_os_str(vec![("PORT", "80"), ("LEVEL", "")]);
+        assert!(validate(&entries, &items, shown).is_ok());
+
+        let items = to_os_str(vec![("PORT", "80a"), ("LEVEL", "")]);
+        let hidden = |k: &OsStr, v: &OsStr| if k == "PORT" { String::from("the secret value") } else { shown(k, v) };
+        assert!(matches!(validate(&entries, &items, hidden), Err(Error::Validation { violations }) if violations == vec![
+            "PORT (line 2): the secret value is not a valid int",
+        ]));
+    }
+}
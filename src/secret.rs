@@ -0,0 +1,55 @@
+//! Classification of keys as secret.
+//!
+//! A key is secret when the template annotates it with `# @secret`, or when it
+//! starts with one of the `--secret-prefix` options. Output formats use this to
+//! keep secret values apart from plain configuration.
+
+use std::ffi::{OsStr, OsString};
+
+use crate::bytes;
+use crate::template::Entry;
+
+#[derive(Debug, Default)]
+pub struct Secrets {
+    prefixes: Vec<String>,
+    keys: Vec<OsString>,
+}
+
+impl Secrets {
+    pub fn new(prefixes: &[String], entries: &[Entry]) -> Self {
+        Secrets {
+            prefixes: prefixes.to_vec(),
+            keys: entries
+                .iter()
+                .filter(|e| e.annotation("secret").is_some())
+                .map(|e| e.key.clone())
+                .collect(),
+        }
+    }
+
+    pub fn is_secret(&self, key: &OsStr) -> bool {
+        self.keys.iter().any(|k| k == key)
+            || self.prefixes.iter().any(|p| bytes::as_bytes(key).starts_with(p.as_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template::Annotation;
+
+    #[test]
+    fn test_is_secret() {
+        let entries = vec![Entry {
+            key: "DB_PASSWORD".into(),
+            value: "".into(),
+            line: 2,
+            annotations: vec![Annotation { name: "secret".into(), argument: "".into(), line: 1 }],
+        }];
+        let secrets = Secrets::new(&[String::from("SECRET_")], &entries);
+        assert!(secrets.is_secret(OsStr::new("DB_PASSWORD")));
+        assert!(secrets.is_secret(OsStr::new("SECRET_KEY")));
+        assert!(!secrets.is_secret(OsStr::new("DB_HOST")));
+        assert!(!Secrets::default().is_secret(OsStr::new("DB_PASSWORD")));
+    }
+}
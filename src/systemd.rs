@@ -0,0 +1,166 @@
+//! Parser for systemd `EnvironmentFile=` files.
+//!
+//! Follows the rules of systemd's own parser:
+//!
+//! * Lines starting with `#` or `;` are comments.
+//! * Unquoted values end at the end of the line and lose trailing whitespace.
+//!   A `\` escapes the next character, and a `\` at the end of a line
+//!   continues the value on the next line.
+//! * Single quoted values are taken literally.
+//! * Double quoted values support `\"`, `\\`, `` \` `` and `\$` escapes and line
+//!   continuations. Other backslashes are kept.
+//! * Quotes are only special at the start of a value or right after a closing
+//!   quote, so `"a"'b'` is `ab` while `a "b"` is taken as is.
+//!
+//! Keys, comments and annotations work as in the `dotenv` module. systemd does
+//! not expand variables, so every `$` is returned escaped as `$$`.
+
+use crate::diagnostic::Diagnostic;
+use crate::dotenv::{parse_dialect, Cursor, Dialect};
+use crate::template::Entry;
+
+/// Parse the contents of a systemd environment file.
+pub fn parse(input: &[u8]) -> Result<(Vec<Entry>, Vec<Diagnostic>), Diagnostic> {
+    parse_dialect(input, Dialect::Systemd)
+}
+
+enum State {
+    /// Before a value or after a closing quote, where quotes open a quoted part.
+    PreValue,
+    Value,
+    SingleQuoted,
+    DoubleQuoted,
+}
+
+impl<'a> Cursor<'a> {
+    /// Read a value, starting right after the `=` and ending before the end of the line.
+    pub(crate) fn systemd_value(&mut self) -> Result<Vec<u8>, Diagnostic> {
+        let mut value = Vec::new();
+        // Length of `value` without unquoted trailing whitespace.
+        let mut trimmed = 0;
+        let mut state = State::PreValue;
+        let mut quote_start = (self.line, self.column);
+
+        loop {
+            let b = match (&state, self.peek()) {
+                (State::PreValue | State::Value, None | Some(b'\n')) => break,
+                (State::SingleQuoted | State::DoubleQuoted, None) => {
+                    return Err(Diagnostic::new(quote_start.0, quote_start.1, "unterminated quoted value"));
+                }
+                (_, Some(b)) => b,
+            };
+            let position = (self.line, self.column);
+            self.next();
+
+            match state {
+                State::PreValue if b == b' ' || b == b'\t' => {}
+                State::PreValue if b == b'\'' || b == b'"' => {
+                    quote_start = position;
+                    state = if b == b'\'' { State::SingleQuoted } else { State::DoubleQuoted };
+                }
+                State::PreValue | State::Value => {
+                    state = State::Value;
+                    if b == b'\\' {
+                        match self.peek() {
+                            Some(b'\n') => {
+                                self.next();
+                            }
+                            Some(c) => {
+                                self.next();
+                                value.push(c);
+                                trimmed = value.len();
+                            }
+                            None => {}
+                        }
+                    } else {
+                        value.push(b);
+                        if b != b' ' && b != b'\t' {
+                            trimmed = value.len();
+                        }
+                    }
+                }
+                State::SingleQuoted => {
+                    if b == b'\'' {
+                        state = State::PreValue;
+                    } else {
+                        value.push(b);
+                    }
+                    trimmed = value.len();
+                }
+                State::DoubleQuoted => {
+                    if b == b'"' {
+                        state = State::PreValue;
+                    } else if b == b'\\' {
+                        match self.next() {
+                            Some(c @ (b'"' | b'\\' | b'`' | b'$')) => value.push(c),
+                            Some(b'\n') => {}
+                            Some(c) => value.extend_from_slice(&[b'\\', c]),
+                            None => {
+                                return Err(Diagnostic::new(quote_start.0, quote_start.1, "unterminated quoted value"));
+                            }
+                        }
+                    } else {
+                        value.push(b);
+                    }
+                    trimmed = value.len();
+                }
+            }
+        }
+
+        value.truncate(trimmed);
+        let mut escaped = Vec::with_capacity(value.len());
+        for b in value {
+            if b == b'$' {
+                escaped.push(b'$');
+            }
+            escaped.push(b);
+        }
+        Ok(escaped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template;
+    use crate::tests::to_os_str;
+
+    #[test]
+    fn test_parse() {
+        let input = concat!(
+            "# comment\n",
+            "; also a comment\n",
+            "A=plain value   \n",
+            "B = 'single \\\\ \"quoted\"'\n",
+            "C=\"double \\\"q\\\" \\\\ \\n \\$x\"\n",
+            "D=\"a\"'b' c\n",
+            "E=a \"b\"\n",
+            "F=one \\\n",
+            "two\\ \n",
+            "G=\"multi\n",
+            "line\"\n",
+            "H=$HOME\n",
+            "I=\n",
+        );
+        let (entries, warnings) = parse(input.as_bytes()).unwrap();
+        let expect = to_os_str(vec![
+            ("A", "plain value"),
+            ("B", "single \\\\ \"quoted\""),
+            ("C", "double \"q\" \\ \\n $$x"),
+            ("D", "abc"),
+            ("E", "a \"b\""),
+            ("F", "one two "),
+            ("G", "multi\nline"),
+            ("H", "$$HOME"),
+            ("I", ""),
+        ]);
+        assert_eq!(template::items(&entries), expect);
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_parse_unterminated_quote() {
+        let result = parse(b"A=1\nB='abc\n");
+        assert_eq!(result, Err(Diagnostic::new(2, 3, "unterminated quoted value")));
+    }
+}
@@ -0,0 +1,125 @@
+//! Parsed template entries and the checks that use their annotations.
+//!
+//! Annotations are comment lines of the form `# @name argument` directly
+//! above an entry. A blank line ends the block of comments that belongs to it.
+
+use std::ffi::OsString;
+
+use crate::{EnvItem, Error};
+
+/// A key and its default value as declared in a template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub key: OsString,
+    pub value: OsString,
+    /// Line of the key in the template, 1-based.
+    pub line: usize,
+    pub annotations: Vec<Annotation>,
+}
+
+/// A `# @name argument` comment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    pub name: String,
+    pub argument: String,
+    pub line: usize,
+}
+
+impl Annotation {
+    /// Parse the text of a comment, without the leading `#`.
+    pub fn parse(comment: &str, line: usize) -> Option<Annotation> {
+        let comment = comment.trim().strip_prefix('@')?;
+        let (name, argument) = comment.split_once(char::is_whitespace).unwrap_or((comment, ""));
+        if name.is_empty() {
+            return None;
+        }
+        Some(Annotation {
+            name: name.to_string(),
+            argument: argument.trim().to_string(),
+            line,
+        })
+    }
+}
+
+impl Entry {
+    pub fn annotation(&self, name: &str) -> Option<&Annotation> {
+        self.annotations.iter().find(|a| a.name == name)
+    }
+
+    /// Whether the entry must end up with a non-empty value. That is the case
+    /// when it is annotated with `@required`, or when `require_values` is set
+    /// and the template gives no default.
+    pub fn is_required(&self, require_values: bool) -> bool {
+        self.annotation("required").is_some() || (require_values && self.value.is_empty())
+    }
+}
+
+/// The key value pairs of the template.
+#[cfg(test)]
+pub fn items(entries: &[Entry]) -> crate::EnvItems {
+    entries.iter().map(|e| (e.key.clone(), e.value.clone())).collect()
+}
+
+/// Check that every required entry has a non-empty value in the merged `items`.
+/// All missing keys are reported at once.
+pub fn check_required(entries: &[Entry], items: &[EnvItem], require_values: bool) -> Result<(), Error> {
+    let missing: Vec<String> = entries
+        .iter()
+        .filter(|e| e.is_required(require_values))
+        .filter(|e| {
+            let value = items.iter().find(|(k, _)| k == &e.key).map(|(_, v)| v);
+            value.is_none_or(|v| v.is_empty())
+        })
+        .map(|e| format!("{} (line {})", e.key.to_string_lossy(), e.line))
+        .collect();
+
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::MissingRequired { keys: missing })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::to_os_str;
+
+    fn entry(key: &str, value: &str, line: usize, annotations: &[&str]) -> Entry {
+        Entry {
+            key: key.into(),
+            value: value.into(),
+            line,
+            annotations: annotations.iter().filter_map(|a| Annotation::parse(a, line - 1)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_annotation_parse() {
+        let a = Annotation::parse(" @enum  debug|info ", 3).unwrap();
+        assert_eq!((a.name.as_str(), a.argument.as_str(), a.line), ("enum", "debug|info", 3));
+        let a = Annotation::parse("@required", 1).unwrap();
+        assert_eq!((a.name.as_str(), a.argument.as_str()), ("required", ""));
+        assert_eq!(Annotation::parse(" plain comment", 1), None);
+        assert_eq!(Annotation::parse(" @ x", 1), None);
+    }
+
+    #[test]
+    fn test_check_required() {
+        let entries = vec![
+            entry("a", "", 2, &["@required"]),
+            entry("b", "", 3, &[]),
+            entry("c", "x", 5, &["@required"]),
+            entry("d", "", 6, &[]),
+        ];
+        let items = to_os_str(vec![("a", ""), ("b", ""), ("c", "x"), ("d", "1")]);
+
+        assert!(matches!(check_required(&entries, &items, false),
+            Err(Error::MissingRequired { keys }) if keys == vec!["a (line 2)"]));
+        assert!(matches!(check_required(&entries, &items, true),
+            Err(Error::MissingRequired { keys }) if keys == vec!["a (line 2)", "b (line 3)"]));
+
+        let items = to_os_str(vec![("a", "1"), ("b", "2"), ("c", "x"), ("d", "")]);
+        assert!(check_required(&entries, &items, false).is_ok());
+    }
+}